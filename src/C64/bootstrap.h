@@ -63,5 +63,9 @@
 #define GDT64_ADDRESS	0x00001000
 #define	GDT64_CODE	0x0008		/* All of memory, R/E */
 #define	GDT64_DATA	0x0010		/* All of memory, R/W */
+#define	GDT64_UDATA	0x0018		/* User data, DPL 3 (see gdt.rs) */
+#define	GDT64_UCODE	0x0020		/* User code, DPL 3 */
+#define	GDT64_TSS	0x0028		/* The TSS, two entries long */
+#define	RPL_USER	0x0003		/* Requested privilege level 3 */
 
 #endif
@@ -42,6 +42,7 @@
 #define	EXIT_FAILURE	1
 #define	EXIT_KILLED	2
 #define	EXIT_BAD_CODE	3
+#define	EXIT_FAULT	4
 
 // some pseudo-Booleans
 
@@ -94,6 +95,11 @@
 #define	E_BAD_ARGS		(-13)
 #define	E_TOO_MANY_ARGS		(-14)
 #define	E_TOO_MANY_ARG_CHARS	(-15)
+#define	E_FAULT			(-16)
+#define	E_NO_PROG		(-17)
+#define	E_BAD_FD		(-18)
+#define	E_NO_MEM		(-19)
+#define	E_PERM			(-20)
 
 // correct (?) way to define NULL
 
@@ -9,6 +9,7 @@
 
 use core::ptr;
 use core::ffi;
+use core::sync::atomic::{compiler_fence, Ordering};
 use spin::Mutex;
 use lazy_static::lazy_static;
 use crate::println;
@@ -30,6 +31,20 @@ extern "C" {
 /// Pinwheel characters
 static pin: [char; 4] = ['|', '/', '-', '\\'];
 
+/// Keeps the time page on a page of its own
+#[repr(C, align(4096))]
+pub struct TimePageBuf {
+    pub page: common::TimePage,
+}
+
+/// The shared time page. Processes read it directly (see ulibs::fast_time)
+/// through a read-only mapping at space::TIME_PAGE; only the clock ISR
+/// writes it.
+#[no_mangle]
+pub static mut __time_page: TimePageBuf = TimePageBuf {
+    page: common::TimePage { seq: 0, ticks: 0, freq: 0 },
+};
+
 /// Clock struct
 pub struct Clock {
     pinwheel: i32,
@@ -53,6 +68,7 @@ impl Clock {
     ///
     pub fn incr_time(&mut self) {
         self.system_time += 1;
+        publish_time(self.system_time);
     }
 
     ///
@@ -90,8 +106,8 @@ pub fn _clk_isr(vector:i32, code:i32) {
 
     // Get current process
     let curr = unsafe { &mut *(scheduler::SCHED.lock().get_curr() as *mut pcbs::Pcb) };
-    let cxt = (curr.cxt as *mut pcbs::Context) as u64;
-    let stk = (curr.stack as *mut stacks::StkBuffer) as u64;
+    let cxt = curr.cxt as u64;
+    let stk = curr.kstack;
     // Decrement its time on the CPU
     curr.ticks -= 1;
     // If no more time, reschedule and dispatch a new proc
@@ -104,6 +120,26 @@ pub fn _clk_isr(vector:i32, code:i32) {
     unsafe { __outb(x86arch::PIC_MASTER_CMD_PORT, x86arch::PIC_EOI) };
 }
 
+///
+/// Copies the system time into the shared time page. The sequence number is
+/// odd while we write, so a reader that raced with us sees it change and
+/// tries again.
+///
+/// param:
+///     t: the new system time
+///
+fn publish_time(t:u64) {
+    unsafe {
+        let page = &mut __time_page.page;
+        let seq  = ptr::read_volatile(&page.seq);
+        ptr::write_volatile(&mut page.seq, seq + 1);
+        compiler_fence(Ordering::SeqCst);
+        ptr::write_volatile(&mut page.ticks, t);
+        compiler_fence(Ordering::SeqCst);
+        ptr::write_volatile(&mut page.seq, seq + 2);
+    }
+}
+
 /// Global clock struct
 lazy_static! {
     pub static ref CLK: Mutex<Clock> = Mutex::new(Clock {
@@ -126,5 +162,8 @@ pub fn _clk_init() {
         __outb(x86arch::TIMER_0_PORT, (divisor >> 8) & 0xff);
     }
 
+    unsafe { ptr::write_volatile(&mut __time_page.page.freq, common::CLOCK_FREQUENCY as u64) };
+    publish_time(CLK.lock().get_time());
+
     interrupt::INT.lock().__install_isr(x86arch::INT_VEC_TIMER, _clk_isr);
 }
@@ -12,3 +12,124 @@ use crate::x86arch;
 pub static CLOCK_FREQUENCY: i32 = 1000;
 pub static TIMER_FREQUENCY: i32 = 1193182;
 pub static DEFAULT_EFLAGS: i32 = (x86arch::EFLAGS_MB1 | x86arch::EFLAGS_IF);
+
+/// Most strings exec will pass to a program in argv, and again in envp
+pub const MAX_ARGUMENTS: usize = 16;
+
+/// Most characters exec will pass in argv and envp, NULs included
+pub const MAX_ARGV_CHARS: usize = 512;
+
+/// Longest program name exec will look up
+pub const MAX_PROG_NAME: usize = 32;
+
+/// Longest program description the registry hands out
+pub const MAX_PROG_DESC: usize = 64;
+
+/// Process priorities; higher priority processes get longer quanta
+pub const PRIO_HIGH: u8 = 0;
+pub const PRIO_STD: u8 = 1;
+pub const PRIO_LOW: u8 = 2;
+pub const NUM_PRIOS: u8 = 3; // priorities are below this
+
+/// Predefined exit status values
+pub const EXIT_SUCCESS: u64 = 0;
+pub const EXIT_FAILURE: u64 = 1;
+pub const EXIT_KILLED: u64 = 2;
+pub const EXIT_BAD_CODE: u64 = 3;
+pub const EXIT_FAULT: u64 = 4; // touched memory it had no business touching
+
+/// Error codes handed back to user code in rax (same values as common.h)
+pub const E_SUCCESS: i64 = 0;
+pub const E_BAD_PID: i64 = -2;
+pub const E_NO_KIDS: i64 = -6;
+pub const E_NO_PCBS: i64 = -7;
+pub const E_NO_STACKS: i64 = -8;
+pub const E_NO_DATA: i64 = -11;
+pub const E_BAD_ARGS: i64 = -13;
+pub const E_TOO_MANY_ARGS: i64 = -14;
+pub const E_TOO_MANY_ARG_CHARS: i64 = -15;
+pub const E_FAULT: i64 = -16;
+pub const E_NO_PROG: i64 = -17;
+pub const E_BAD_FD: i64 = -18;
+pub const E_NO_MEM: i64 = -19;
+pub const E_PERM: i64 = -20;
+
+/// File descriptors every process starts with
+pub const STDIN: u64 = 0;
+pub const STDOUT: u64 = 1;
+pub const STDERR: u64 = 2;
+pub const STDAUX: u64 = 3; // the serial port
+
+/// Protection bits for sys_mmap and sys_mprotect
+pub const PROT_NONE: u64 = 0x0;
+pub const PROT_READ: u64 = 0x1;
+pub const PROT_WRITE: u64 = 0x2;
+pub const PROT_EXEC: u64 = 0x4;
+pub const PROT_ALL: u64 = PROT_READ | PROT_WRITE | PROT_EXEC;
+
+/// sys_mmap flags; anonymous memory is all there is for now
+pub const MAP_ANON: u64 = 0x1;
+
+/// Biggest region sys_mmap will hand out
+pub const MMAP_MAX: u64 = 256 * 1024;
+
+/// What happens when a process makes a syscall sys_restrict took away
+pub const SYSPOL_EPERM: u8 = 0; // the call fails with E_PERM
+pub const SYSPOL_KILL: u8 = 1;  // the process is terminated with EXIT_KILLED
+
+/// Syscall trace flags (see sys_trace)
+pub const TRACE_ON: u8 = 0x01;      // log every syscall this process makes
+pub const TRACE_INHERIT: u8 = 0x02; // children start with the same flags
+
+/// Layout of the shared time page. The clock ISR keeps it up to date and
+/// user code reads it with ulibs::fast_time() instead of calling sys_time.
+#[repr(C)]
+pub struct TimePage {
+    pub seq: u64,   // bumped before and after each update; odd mid-update
+    pub ticks: u64, // system time, in clock ticks
+    pub freq: u64,  // clock ticks per second
+}
+
+/// What sys_progs hands back about one registered program. The strings are
+/// NUL padded.
+#[repr(C)]
+pub struct ProgInfo {
+    pub name: [u8; MAX_PROG_NAME],
+    pub desc: [u8; MAX_PROG_DESC],
+    pub prio: u8,
+}
+
+/// What sys_procs hands back about one live process. The stack figures are
+/// high-water marks, for tuning stacks::STACK_SIZE and the stack limit.
+#[repr(C)]
+pub struct ProcInfo {
+    pub pid: u16,
+    pub ppid: u16,
+    pub state: u8,
+    pub prio: u8,
+    pub _pad: [u8; 2],    // zeroed, so no uninitialised bytes get copied out
+    pub stack_used: u64,  // bytes of user stack ever touched
+    pub stack_limit: u64, // how far the user stack may grow
+    pub kstack_used: u64, // bytes of kernel stack ever touched
+    pub kstack_size: u64,
+}
+
+/// Most fd remappings sys_spawn takes
+pub const MAX_SPAWN_FDS: usize = 8;
+
+/// Which SpawnAttrs fields sys_spawn should look at
+pub const SPAWN_PRIO: u64 = 0x01;   // use prio instead of the program's own
+pub const SPAWN_PGROUP: u64 = 0x02; // use pgid instead of the parent's group
+pub const SPAWN_FDS: u64 = 0x04;    // apply the fd remappings in fds
+pub const SPAWN_STACK: u64 = 0x08;  // let the stack grow to stack_limit bytes
+
+/// Optional attributes for sys_spawn. Fields only count if their flag is set.
+#[repr(C)]
+pub struct SpawnAttrs {
+    pub flags: u64,                     // SPAWN_* bits
+    pub stack_limit: u64,               // whole pages, STACK_BYTES up to space::STACK_MAX
+    pub pgid: u16,                      // an existing process group; 0 starts a new one
+    pub prio: u8,                       // priority (PRIO_*)
+    pub nfds: u8,                       // how many entries of fds are used
+    pub fds: [[u8; 2]; MAX_SPAWN_FDS],  // child fd [1] gets parent fd [0]
+}
@@ -0,0 +1,162 @@
+///
+/// fault.rs
+///
+/// Author: Jonathan Schenk
+///
+/// Handlers for processor exceptions the kernel can do something about.
+///
+////////////////////////////////////////////////////////////////////////////////
+
+use core::fmt;
+use crate::println;
+use crate::x86arch;
+use crate::common;
+use crate::interrupt;
+use crate::paging;
+use crate::pcbs;
+use crate::gdt;
+use crate::scheduler;
+use crate::stacks;
+use crate::space;
+use crate::syscalls;
+use crate::uaccess;
+
+extern "C" {
+    #[no_mangle]
+    fn __get_cr2() -> u64;
+}
+
+/// Page fault error code bits
+const PF_PRESENT: i32 = 0x01; // the page was there; the access wasn't allowed
+const PF_WRITE: i32   = 0x02; // it was a write
+const PF_USER: i32    = 0x04; // it came from user mode
+const PF_RSVD: i32    = 0x08; // a reserved bit was set in a page table entry
+const PF_INSTR: i32   = 0x10; // it was an instruction fetch
+
+/// A page fault, for printing
+struct PageFault {
+    addr: u64,
+    code: i32,
+}
+
+impl fmt::Display for PageFault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "page fault at {:#x}: {}, {} in {} mode", self.addr,
+               if self.code & PF_PRESENT != 0 { "protection" } else { "not present" },
+               if self.code & PF_INSTR != 0 { "fetch" }
+               else if self.code & PF_WRITE != 0 { "write" }
+               else { "read" },
+               if self.code & PF_USER != 0 { "user" } else { "kernel" })?;
+        if self.code & PF_RSVD != 0 {
+            write!(f, ", reserved bit set")?;
+        }
+        return Ok(());
+    }
+}
+
+/// Stops the OS over a fault the kernel caused
+fn fatal(what: fmt::Arguments, rip: u64) -> ! {
+    unsafe { asm!("CLI") };
+    println!("\nKERNEL FAULT: {} at rip {:#x}", what, rip);
+    loop {}
+}
+
+///
+/// ISR for page faults. A write to a copy-on-write page gets the page
+/// copied and is retried, whether the process or the kernel on its behalf
+/// did it, and so does a fault just below the bottom of the process'
+/// stack once the stack has grown to cover it, and so does the first touch
+/// of the heap once it's been reserved. A fault in one of the user
+/// copy routines gets fixed up so the syscall can fail with E_FAULT. Any
+/// other fault in a process terminates it with EXIT_FAULT, after saying
+/// what happened (running past its stack limit gets called a stack
+/// overflow); one in the kernel is fatal.
+///
+/// params: the usual for isrs
+///
+fn _pf_isr(_vector:i32, code:i32) {
+    let addr = unsafe { __get_cr2() };
+    if code & (PF_PRESENT | PF_WRITE) == PF_PRESENT | PF_WRITE && paging::cow_fault(addr) {
+        return;
+    }
+    if code & PF_PRESENT == 0 && addr >= space::USER_BASE {
+        let curr = unsafe { &mut *(scheduler::SCHED.lock().get_curr() as *mut pcbs::Pcb) };
+        if stacks::stack_grow(curr, addr) || stacks::heap_fault(curr, addr) {
+            return;
+        }
+    }
+
+    let fault = PageFault { addr: addr, code: code };
+    if interrupt::nested() {
+        let cxt = interrupt::kern_cxt();
+        if !uaccess::fixup_exception(cxt) {
+            fatal(format_args!("{}", fault), cxt.rip);
+        }
+        return;
+    }
+
+    let curr = unsafe { &mut *(scheduler::SCHED.lock().get_curr() as *mut pcbs::Pcb) };
+    if addr >= space::STACK_GUARD && addr < space::STACK_TOP - curr.stack_limit {
+        println!("stack overflow in pid {} at rip {:#x}, terminated", curr.pid, curr.cxt().rip);
+    }
+    else {
+        println!("pid {}: {} at rip {:#x}, terminated", curr.pid, fault, curr.cxt().rip);
+    }
+    syscalls::terminate(curr, common::EXIT_FAULT);
+}
+
+///
+/// ISR for general protection faults. The user copy routines get the same
+/// fixup as for page faults. A process that causes one (a privileged
+/// instruction in ring 3, say) is terminated with EXIT_KILLED; the kernel
+/// causing one is fatal.
+///
+/// params: the usual for isrs
+///
+fn _gp_isr(vector:i32, code:i32) {
+    if interrupt::nested() {
+        let cxt = interrupt::kern_cxt();
+        if !uaccess::fixup_exception(cxt) {
+            fatal(format_args!("vector {:#x}, code {:#x}", vector, code), cxt.rip);
+        }
+        return;
+    }
+
+    let curr = unsafe { &mut *(scheduler::SCHED.lock().get_curr() as *mut pcbs::Pcb) };
+    println!("pid {}: general protection fault, code {:#x} at rip {:#x}, killed",
+             curr.pid, code, curr.cxt().rip);
+    syscalls::terminate(curr, common::EXIT_KILLED);
+}
+
+///
+/// ISR for double faults, which runs on its own stack (see gdt.rs). The
+/// one we expect is a page fault that couldn't be delivered because the
+/// kernel ran its stack into the guard page below it; there's no telling
+/// what state the kernel was left in, so it's fatal either way. The kernel
+/// may have been holding SCHED when that happened, so this never locks it:
+/// RSP0 in the TSS says whose kernel stack was in use.
+///
+/// params:
+///     the usual for isrs
+///
+fn _df_isr(_vector:i32, _code:i32) {
+    let addr = unsafe { __get_cr2() };
+    let kstk = gdt::rsp0() - stacks::STACK_BYTES;
+    if !interrupt::nested() {
+        fatal(format_args!("double fault from user mode, cr2 {:#x}", addr), 0);
+    }
+    let rip = interrupt::kern_cxt().rip;
+    if stacks::is_guard(kstk, addr) {
+        fatal(format_args!("stack overflow in kernel stack {:#x}", kstk), rip);
+    }
+    fatal(format_args!("double fault, cr2 {:#x}", addr), rip);
+}
+
+/// Install the fault handlers
+pub fn _fault_init() {
+    println!("FAULT");
+    interrupt::INT.lock().__install_isr(x86arch::INT_VEC_DOUBLE_FAULT, _df_isr);
+    interrupt::INT.lock().set_idt_ist(x86arch::INT_VEC_DOUBLE_FAULT, gdt::IST_DOUBLE_FAULT);
+    interrupt::INT.lock().__install_isr(x86arch::INT_VEC_GENERAL_PROTECTION, _gp_isr);
+    interrupt::INT.lock().__install_isr(x86arch::INT_VEC_PAGE_FAULT, _pf_isr);
+}
@@ -0,0 +1,185 @@
+///
+/// files.rs
+///
+/// Author: Jonathan Schenk
+///
+/// Things a file descriptor can point at. Every device (and later, pipes
+/// and real files) implements File, and each process has a table of them.
+///
+////////////////////////////////////////////////////////////////////////////////
+
+use crate::c_io;
+use crate::common;
+use crate::pcbs;
+use crate::println;
+use crate::x86arch;
+
+extern "C" {
+    #[no_mangle]
+    fn __inb(port:i32) -> i32;
+    #[no_mangle]
+    fn __outb(port:i32, value:i32);
+}
+
+/// How many file descriptors each process has
+pub const MAX_FDS: usize = 16;
+
+/// A process' file descriptor table
+pub type FdTable = [Option<&'static dyn File>; MAX_FDS];
+
+/// Anything a file descriptor can refer to. Reads and writes return how
+/// many bytes they moved, or a negative error code.
+pub trait File: Sync {
+    /// Name for listings
+    fn name(&self) -> &'static str;
+
+    /// Reads up to buf.len() bytes. Doesn't wait for more to show up.
+    fn read(&self, buf: &mut [u8]) -> i64;
+
+    /// Writes buf
+    fn write(&self, buf: &[u8]) -> i64;
+
+    /// Called each time another descriptor starts pointing at this file
+    fn open(&self) {}
+
+    /// Called each time a descriptor pointing at this file goes away
+    fn close(&self) {}
+}
+
+/// The screen. There's no keyboard driver, so reading finds nothing.
+pub struct Console;
+
+impl File for Console {
+    fn name(&self) -> &'static str {
+        return "console";
+    }
+
+    fn read(&self, _buf: &mut [u8]) -> i64 {
+        return 0;
+    }
+
+    fn write(&self, buf: &[u8]) -> i64 {
+        let mut w = c_io::WRITER.lock();
+        for c in buf.iter() {
+            w.c_putchar(*c);
+        }
+        return buf.len() as i64;
+    }
+}
+
+/// COM1, polled
+pub struct Serial;
+
+impl File for Serial {
+    fn name(&self) -> &'static str {
+        return "serial";
+    }
+
+    fn read(&self, buf: &mut [u8]) -> i64 {
+        let mut n = 0;
+        while n < buf.len() && unsafe { __inb(x86arch::UA4_LSR) } & x86arch::UA4_LSR_RXDA != 0 {
+            buf[n] = unsafe { __inb(x86arch::UA4_RXD) } as u8;
+            n += 1;
+        }
+        return n as i64;
+    }
+
+    fn write(&self, buf: &[u8]) -> i64 {
+        for c in buf.iter() {
+            while unsafe { __inb(x86arch::UA4_LSR) } & x86arch::UA4_LSR_TXRDY == 0 {}
+            unsafe { __outb(x86arch::UA4_TXD, *c as i32) };
+        }
+        return buf.len() as i64;
+    }
+}
+
+pub static CONSOLE: Console = Console;
+pub static SERIAL: Serial = Serial;
+
+///
+/// std_fds - the table init starts with
+///
+/// returns:
+///     stdin, stdout and stderr on the console, STDAUX on the serial port
+///
+pub fn std_fds() -> FdTable {
+    let mut fds: FdTable = [None; MAX_FDS];
+    fds[common::STDIN as usize]  = Some(&CONSOLE);
+    fds[common::STDOUT as usize] = Some(&CONSOLE);
+    fds[common::STDERR as usize] = Some(&CONSOLE);
+    fds[common::STDAUX as usize] = Some(&SERIAL);
+    return fds;
+}
+
+///
+/// get - finds the file behind a descriptor
+///
+/// params:
+///     pcb: the process
+///     fd: the descriptor
+///
+/// returns:
+///     the file, or None if fd isn't open
+///
+pub fn get(pcb: &pcbs::Pcb, fd: u64) -> Option<&'static dyn File> {
+    if fd as usize >= MAX_FDS {
+        return None;
+    }
+    return pcb.fds[fd as usize];
+}
+
+///
+/// set - points a descriptor at a file, closing whatever was there
+///
+/// params:
+///     fds: the table
+///     fd: the descriptor, already checked to be in range
+///     file: what it should point at
+///
+pub fn set(fds: &mut FdTable, fd: usize, file: Option<&'static dyn File>) {
+    if let Some(f) = file {
+        f.open();
+    }
+    if let Some(old) = fds[fd] {
+        old.close();
+    }
+    fds[fd] = file;
+}
+
+///
+/// inherit - gives a new process a copy of its parent's descriptors
+///
+/// params:
+///     dst: the child's table
+///     src: the parent's table
+///
+pub fn inherit(dst: &mut FdTable, src: &FdTable) {
+    for i in 0..MAX_FDS {
+        set(dst, i, src[i]);
+    }
+}
+
+///
+/// close_all - closes every descriptor in a table
+///
+pub fn close_all(fds: &mut FdTable) {
+    for i in 0..MAX_FDS {
+        set(fds, i, None);
+    }
+}
+
+///
+/// Sets up the serial port
+///
+pub fn _files_init() {
+    unsafe {
+        __outb(x86arch::UA4_IER, 0);
+        __outb(x86arch::UA4_LCR, x86arch::UA4_LCR_BKSE);
+        __outb(x86arch::UA4_LBGD_L, x86arch::BAUD_38400);
+        __outb(x86arch::UA4_LBGD_H, 0);
+        __outb(x86arch::UA4_LCR, x86arch::UA4_LCR_BITS_8);
+        __outb(x86arch::UA4_FCR, x86arch::UA5_FCR_FIFO_EN | x86arch::UA5_FCR_RXSR | x86arch::UA5_FCR_TXSR);
+        __outb(x86arch::UA4_MCR, x86arch::UA4_MCR_DTR | x86arch::UA4_MCR_RTS);
+    }
+    println!("FILES: {} {}", CONSOLE.name(), SERIAL.name());
+}
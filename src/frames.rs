@@ -0,0 +1,348 @@
+///
+/// frames.rs
+///
+/// Author: Jonathan Schenk
+///
+/// Physical memory. The BIOS memory map tells us what RAM there is, and a
+/// bitmap with one bit per 4K frame keeps track of which frames are in use,
+/// and a reference count per frame lets processes share them (see fork).
+/// The bitmap is small enough for the kernel heap; the reference counts,
+/// a byte per frame, aren't, so they go in frames of their own once
+/// paging has mapped all of RAM (see _frames_refs_init).
+/// Everything below kalloc::HEAP_LIMIT belongs to the kernel (the BIOS
+/// area, the boot tables, the image and the kernel heap), so frames handed
+/// out here all come from above it.
+///
+////////////////////////////////////////////////////////////////////////////////
+
+use core::ptr;
+use core::slice;
+use spin::Mutex;
+use lazy_static::lazy_static;
+use crate::kalloc;
+use crate::println;
+
+extern "C" {
+    #[no_mangle]
+    static _end: u8;
+    #[no_mangle]
+    static __bss_end: u8;
+}
+
+/// Where the BIOS leaves its memory map (see bootstrap.h)
+const MMAP_ADDRESS: u64 = 0x2D00;
+
+/// E820 region types and ACPI 3.0 bits
+const REGION_USABLE: u32 = 1;
+const REGION_IGNORE: u32 = 0x01;
+const REGION_NONVOL: u32 = 0x02;
+
+/// We don't look at memory above 4G
+const PHYS_LIMIT: u64 = 1 << 32;
+
+/// Size of a frame
+pub const FRAME_SIZE: u64 = 4096;
+
+/// Memory region information returned by the BIOS
+#[repr(C, packed)]
+struct Region {
+    base: u64,
+    length: u64,
+    kind: u32,
+    acpi: u32,
+}
+
+///
+/// for_each_usable - calls f with every region of normal RAM in the BIOS
+/// memory map, clipped to PHYS_LIMIT
+///
+/// param:
+///     f: gets the base and end (one past the last byte) of each region
+///
+pub fn for_each_usable<F: FnMut(u64, u64)>(mut f: F) {
+    // entries == -1 could happen
+    let entries = unsafe { ptr::read_unaligned(MMAP_ADDRESS as *const i32) };
+    for i in 0..entries.max(0) as u64 {
+        let r = unsafe {
+            ptr::read_unaligned((MMAP_ADDRESS + 4 + i * 24) as *const Region)
+        };
+        if r.acpi & REGION_IGNORE == 0 || r.acpi & REGION_NONVOL != 0 ||
+            r.kind != REGION_USABLE || r.base >= PHYS_LIMIT {
+            continue;
+        }
+        f(r.base, (r.base + r.length).min(PHYS_LIMIT));
+    }
+}
+
+///
+/// image_end - where the kernel image, BSS included, stops
+///
+/// returns:
+///     the end of the image rounded up to 64K, like the C kmalloc did
+///
+pub fn image_end() -> u64 {
+    let end = unsafe {
+        (&_end as *const u8 as u64).max(&__bss_end as *const u8 as u64)
+    };
+    return (end + 0xffff) & !0xffff;
+}
+
+/// The frame bitmap; a set bit is a frame in use or that isn't RAM
+pub struct FrameMap {
+    bits: &'static mut [u64],
+    refs: &'static mut [u8], // how many mappings each frame handed out has
+    nframes: u64, // frames the bitmap covers
+    total: u64,   // frames of RAM
+    free: u64,    // frames of RAM not in use
+    next: usize,  // word to start looking in
+}
+
+impl FrameMap {
+
+    /// Marks frames [first, last) free or used
+    fn mark(&mut self, first: u64, last: u64, used: bool) {
+        for f in first..last.min(self.nframes) {
+            let bit = 1 << (f % 64);
+            let word = &mut self.bits[(f / 64) as usize];
+            if used && *word & bit == 0 {
+                *word |= bit;
+                self.free -= 1;
+            }
+            else if !used && *word & bit != 0 {
+                *word &= !bit;
+                self.free += 1;
+            }
+        }
+    }
+
+    ///
+    /// alloc - takes a free frame
+    ///
+    /// returns:
+    ///     physical address of the frame, or 0 if there are none left
+    ///
+    pub fn alloc(&mut self) -> u64 {
+        let words = self.bits.len();
+        for i in 0..words {
+            let w = (self.next + i) % words;
+            if self.bits[w] != !0 {
+                let b = (!self.bits[w]).trailing_zeros() as u64;
+                let f = w as u64 * 64 + b;
+                if f >= self.nframes {
+                    continue;
+                }
+                self.bits[w] |= 1 << b;
+                self.refs[f as usize] = 1;
+                self.free -= 1;
+                self.next = w;
+                return f * FRAME_SIZE;
+            }
+        }
+        return 0;
+    }
+
+    ///
+    /// free - drops a reference to a frame, and gives it back when that
+    /// was the last one
+    ///
+    /// param:
+    ///     frame: physical address from alloc
+    ///
+    /// Panics if the frame isn't in use, since that means someone's
+    /// bookkeeping is broken.
+    ///
+    pub fn free(&mut self, frame: u64) {
+        let f = self.handed_out(frame);
+        self.refs[f] -= 1;
+        if self.refs[f] == 0 {
+            self.mark(f as u64, f as u64 + 1, false);
+        }
+    }
+
+    ///
+    /// share - adds a reference to a frame
+    ///
+    /// param:
+    ///     frame: physical address from alloc
+    ///
+    pub fn share(&mut self, frame: u64) {
+        let f = self.handed_out(frame);
+        if self.refs[f] == u8::max_value() {
+            panic!("too many references to frame {:#x}", frame);
+        }
+        self.refs[f] += 1;
+    }
+
+    ///
+    /// refs - how many references a frame has
+    ///
+    /// param:
+    ///     frame: physical address from alloc
+    ///
+    pub fn refs(&self, frame: u64) -> u8 {
+        let f = frame / FRAME_SIZE;
+        if f >= self.nframes {
+            return 0;
+        }
+        return self.refs[f as usize];
+    }
+
+    ///
+    /// take_run - takes frames in a row, from above the kernel heap
+    ///
+    /// param:
+    ///     count: how many
+    ///
+    /// returns:
+    ///     physical address of the first, or 0 if there's no run that long
+    ///
+    fn take_run(&mut self, count: u64) -> u64 {
+        let mut start = kalloc::HEAP_LIMIT / FRAME_SIZE;
+        let mut f = start;
+        while f < self.nframes && f - start < count {
+            if self.bits[(f / 64) as usize] & (1 << (f % 64)) != 0 {
+                start = f + 1;
+            }
+            f += 1;
+        }
+        if f - start < count {
+            return 0;
+        }
+        self.mark(start, start + count, true);
+        return start * FRAME_SIZE;
+    }
+
+    /// Index of a frame from alloc; panics if it isn't one
+    fn handed_out(&self, frame: u64) -> usize {
+        let f = frame / FRAME_SIZE;
+        if frame % FRAME_SIZE != 0 || f >= self.nframes || self.refs[f as usize] == 0 {
+            panic!("{:#x} isn't an allocated frame", frame);
+        }
+        return f as usize;
+    }
+
+    /// One past the highest frame of RAM
+    pub fn top(&self) -> u64 {
+        return self.nframes * FRAME_SIZE;
+    }
+
+    /// Frames of RAM there are
+    pub fn total(&self) -> u64 {
+        return self.total;
+    }
+
+    /// Frames of RAM nobody is using
+    pub fn free_count(&self) -> u64 {
+        return self.free;
+    }
+}
+
+/// Our global frame bitmap
+lazy_static! {
+    pub static ref FRAMES: Mutex<FrameMap> = Mutex::new(FrameMap {
+        bits: &mut [],
+        refs: &mut [],
+        nframes: 0,
+        total: 0,
+        free: 0,
+        next: 0,
+    });
+}
+
+///
+/// frame_alloc - takes a free 4K frame
+///
+/// returns:
+///     physical address of the frame, or 0 if there are none left
+///
+pub fn frame_alloc() -> u64 {
+    return FRAMES.lock().alloc();
+}
+
+///
+/// frame_free - drops a reference to a frame from frame_alloc, freeing it
+/// if that was the last
+///
+/// param:
+///     frame: physical address of the frame
+///
+pub fn frame_free(frame: u64) {
+    FRAMES.lock().free(frame);
+}
+
+///
+/// frame_share - adds a reference to a frame from frame_alloc
+///
+/// param:
+///     frame: physical address of the frame
+///
+pub fn frame_share(frame: u64) {
+    FRAMES.lock().share(frame);
+}
+
+///
+/// frame_refs - how many references a frame has
+///
+/// param:
+///     frame: physical address of the frame
+///
+pub fn frame_refs(frame: u64) -> u8 {
+    return FRAMES.lock().refs(frame);
+}
+
+///
+/// Builds the frame bitmap from the BIOS memory map and reports how much
+/// memory there is. The kernel heap has to be up first, since the bitmap
+/// lives in it. No frames can be handed out until _frames_refs_init.
+///
+pub fn _frames_init() {
+    let mut top = 0;
+    for_each_usable(|_base, end| top = top.max(end));
+    let nframes = top / FRAME_SIZE;
+    let words = ((nframes + 63) / 64) as usize;
+
+    let mem = kalloc::kmalloc(words as u64 * 8);
+    if mem == 0 {
+        panic!("no memory for a bitmap of {} frames", nframes);
+    }
+
+    let mut map = FRAMES.lock();
+    map.bits = unsafe { slice::from_raw_parts_mut(mem as *mut u64, words) };
+    for w in map.bits.iter_mut() {
+        *w = !0;
+    }
+    map.nframes = nframes;
+
+    // Free the RAM, then take back what the kernel already owns: low
+    // memory, the image and everything the kernel heap was built from
+    for_each_usable(|base, end| {
+        let first = (base + FRAME_SIZE - 1) / FRAME_SIZE;
+        map.mark(first, end / FRAME_SIZE, false);
+    });
+    map.total = map.free;
+    map.mark(0, image_end() / FRAME_SIZE, true);
+    map.mark(0, kalloc::HEAP_LIMIT / FRAME_SIZE, true);
+
+    println!("FRAMES: {}K total, {}K free",
+             map.total * FRAME_SIZE / 1024, map.free * FRAME_SIZE / 1024);
+}
+
+///
+/// Sets up the reference counts, in frames taken from the bitmap, which
+/// stay in use for good. Has to come after paging::_paging_init, since the
+/// boot page tables only map the first 2M.
+///
+pub fn _frames_refs_init() {
+    let mut map = FRAMES.lock();
+    let nframes = map.nframes;
+    let len  = (nframes + FRAME_SIZE - 1) / FRAME_SIZE;
+    let refs = map.take_run(len);
+    if refs == 0 {
+        panic!("no room for the reference counts of {} frames", nframes);
+    }
+    map.refs = unsafe { slice::from_raw_parts_mut(refs as *mut u8, nframes as usize) };
+    for r in map.refs.iter_mut() {
+        *r = 0;
+    }
+    println!("FRAMES: {}K of reference counts at {:#x}", len * FRAME_SIZE / 1024, refs);
+}
@@ -0,0 +1,122 @@
+///
+/// gdt.rs
+///
+/// Author: Jonathan Schenk
+///
+/// User segments and the TSS. long_mode.S leaves a GDT with just the
+/// kernel's code and data segments in it; this adds user data and code
+/// segments, in the order SYSRET wants them (see syscalls::SYSRET_SEL_BASE),
+/// and a TSS. The TSS holds RSP0, the stack the CPU switches to when an
+/// interrupt comes in from user mode, which is the running process' kernel
+/// stack (see scheduler::_dispatch), and a stack of its own for double
+/// faults, which is what overflowing a kernel stack turns into.
+///
+////////////////////////////////////////////////////////////////////////////////
+
+use core::mem;
+use crate::x86arch;
+use crate::println;
+
+extern "C" {
+    #[no_mangle]
+    fn __lgdt(base:u64, limit:u16);
+    #[no_mangle]
+    fn __ltr(selector:u16);
+}
+
+/// Descriptors for the user segments: present, DPL 3, data read/write and
+/// 64-bit code execute/read
+const UDATA_DESC: u64 = 0x0000_f200_0000_0000;
+const UCODE_DESC: u64 = 0x0020_fa00_0000_0000;
+
+/// Present, available 64-bit TSS
+const TSS_TYPE: u64 = 0x89;
+
+/// Entries in the GDT, the TSS counting as two
+const GDT_ENTRIES: u64 = 7;
+
+/// Interrupt stack double faults run on
+pub const IST_DOUBLE_FAULT: usize = 1;
+
+/// Words in an interrupt stack
+const IST_SIZE: usize = 512;
+
+/// Double faults get a stack of their own, since the one they came in on
+/// is likely the problem
+static mut DF_STACK: [u64; IST_SIZE] = [0; IST_SIZE];
+
+/// 64-bit task state segment, as the manual lays it out
+#[repr(C, packed)]
+pub struct Tss {
+    reserved0: u32,
+    rsp: [u64; 3],   // stacks for CPL 0-2
+    reserved1: u64,
+    ist: [u64; 7],   // interrupt stack table
+    reserved2: u64,
+    reserved3: u16,
+    iomap: u16,      // offset of the I/O bitmap; past the end means none
+}
+
+/// The TSS. The CPU reads RSP0 out of it on every interrupt from user mode,
+/// and __syscall_entry reads it too, since SYSCALL doesn't switch stacks.
+#[no_mangle]
+pub static mut __tss: Tss = Tss {
+    reserved0: 0,
+    rsp: [0; 3],
+    reserved1: 0,
+    ist: [0; 7],
+    reserved2: 0,
+    reserved3: 0,
+    iomap: mem::size_of::<Tss>() as u16,
+};
+
+///
+/// set_rsp0 - sets the stack interrupts from user mode land on
+///
+/// param:
+///     rsp: top of the running process' kernel stack
+///
+pub fn set_rsp0(rsp: u64) {
+    unsafe { __tss.rsp[0] = rsp };
+}
+
+///
+/// rsp0 - gets the stack interrupts from user mode land on
+///
+/// returns:
+///     top of the running process' kernel stack
+///
+pub fn rsp0() -> u64 {
+    return unsafe { __tss.rsp[0] };
+}
+
+/// Writes a GDT entry
+fn set_entry(sel: u64, desc: u64) {
+    unsafe { *((x86arch::GDT64_ADDRESS + sel) as *mut u64) = desc };
+}
+
+///
+/// Adds the user segments and the TSS to the GDT and loads the task
+/// register. With no I/O bitmap and IOPL 0, user mode can't touch ports.
+///
+pub fn _gdt_init() {
+    println!("GDT");
+    let base  = unsafe { &__tss as *const Tss } as u64;
+    let limit = mem::size_of::<Tss>() as u64 - 1;
+    unsafe {
+        let df = &DF_STACK as *const [u64; IST_SIZE] as u64;
+        __tss.ist[IST_DOUBLE_FAULT - 1] = df + (IST_SIZE * 8) as u64;
+    }
+
+    set_entry(x86arch::GDT64_UDATA, UDATA_DESC);
+    set_entry(x86arch::GDT64_UCODE, UCODE_DESC);
+    set_entry(x86arch::GDT64_TSS,
+              (limit & 0xffff) | (base & 0xff_ffff) << 16 | TSS_TYPE << 40 |
+              ((limit >> 16) & 0xf) << 48 | ((base >> 24) & 0xff) << 56);
+    set_entry(x86arch::GDT64_TSS + 8, base >> 32);
+
+    unsafe {
+        __lgdt(x86arch::GDT64_ADDRESS, (GDT_ENTRIES * 8 - 1) as u16);
+        __ltr(x86arch::GDT64_TSS as u16);
+    }
+}
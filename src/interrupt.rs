@@ -13,25 +13,31 @@ use spin::Mutex;
 use lazy_static::lazy_static;
 use crate::println;
 use crate::x86arch;
+use crate::pcbs;
+use alloc::boxed::Box;
 
 /// External things we need :)
 extern "C" {
     #[no_mangle]
     fn __outb(port:i32, value:i32);
     #[no_mangle]
-    fn _kmalloc(size:u64) -> usize;
-    #[no_mangle]
     static __isr_stub_table: usize;
+    #[no_mangle]
+    static __isr_depth: u64;
+    #[no_mangle]
+    fn __get_cs() -> u64;
 }
 
 /// ISR table info that isn't really necessarily true
 const ISR_TAB_USIZE: usize = 256;
-const ISR_TAB_SIZE: u64 = 256;
 static IDT_ADDRESS: usize = 0x00001100;
 
+/// Context of the kernel code a nested interrupt broke into, 0 if none
+static mut KERN_CXT: u64 = 0;
+
 /// ISR table
 pub struct Interrupt {
-    isr_table: &'static mut Buffer,
+    isr_table: Box<Buffer>,
 }
 
 /// 64 bit IDT gate struct defined by the manual
@@ -95,6 +101,33 @@ impl Interrupt {
         g.zero = 0x00000000;
     }
 
+    ///
+    /// Lets user mode use an IDT entry with an int instruction; otherwise
+    /// that's a general protection fault
+    ///
+    /// param:
+    ///     entry: location in table
+    ///
+    pub fn set_idt_user(&mut self, entry:usize) {
+        let addr = (IDT_ADDRESS + entry * 16) as usize;
+        let g = unsafe { &mut *((addr) as *mut idt_gate) };
+        g.flags |= x86arch::IDT_DPL_3 as u16;
+    }
+
+    ///
+    /// Makes an IDT entry switch to one of the TSS' interrupt stacks, for
+    /// exceptions that can't trust the stack they interrupted
+    ///
+    /// params:
+    ///     entry: location in table
+    ///     ist: which stack, 1-7 (see gdt.rs)
+    ///
+    pub fn set_idt_ist(&mut self, entry:usize, ist:usize) {
+        let addr = (IDT_ADDRESS + entry * 16) as usize;
+        let g = unsafe { &mut *((addr) as *mut idt_gate) };
+        g.flags = (g.flags & !(x86arch::IDT_IST_MASK as u16)) | ist as u16;
+    }
+
     ///
     /// Installs an ISR
     ///
@@ -111,6 +144,16 @@ impl Interrupt {
         return old_handler;
     }
 
+    ///
+    /// Gets the ISR installed for a vector
+    ///
+    /// param:
+    ///     vector: interrupt vec we want the ISR for
+    ///
+    pub fn get_isr(&mut self, vector:usize) -> fn(i32, i32) {
+        return self.isr_table.data[vector];
+    }
+
     ///
     /// Initializes the PIC
     ///
@@ -137,7 +180,7 @@ impl Interrupt {
     /// Returns the address of the ISR table
     ///
     pub fn isr_tab(&mut self) -> u64 {
-        let raw = self.isr_table as *mut Buffer;
+        let raw = &mut *self.isr_table as *mut Buffer;
         return raw as u64;
     }
 }
@@ -197,7 +240,9 @@ fn __default_mystery_handler(vector:i32, code:i32) {
 /// Global interrupt struct
 lazy_static! {
     pub static ref INT: Mutex<Interrupt> = Mutex::new(Interrupt {
-        isr_table: unsafe { &mut *(_kmalloc(ISR_TAB_SIZE * 8) as *mut Buffer) },
+        isr_table: Box::new(Buffer {
+            data: [__default_unexpected_handler; ISR_TAB_USIZE],
+        }),
     });
 }
 
@@ -211,6 +256,51 @@ pub fn get_isr_table() -> u64 {
     return ret;
 }
 
+///
+/// Called by isr_nested in isr_stubs.S for interrupts taken while we were
+/// already in the kernel. Remembers the interrupted kernel context for the
+/// ISR (see kern_cxt) and calls it.
+///
+/// param:
+///     cxt: the kernel context saved on the interrupted stack
+///
+#[no_mangle]
+pub extern "C" fn __isr_nested(cxt: u64) {
+    let c       = unsafe { &*(cxt as *const pcbs::Context) };
+    let vector  = c.vector as usize;
+    let code    = c.code as i32;
+    let handler = INT.lock().get_isr(vector);
+
+    let outer = unsafe { KERN_CXT };
+    unsafe { KERN_CXT = cxt };
+    handler(vector as i32, code);
+    unsafe { KERN_CXT = outer };
+}
+
+///
+/// Tells an ISR whether it interrupted kernel code rather than a process
+///
+pub fn nested() -> bool {
+    return unsafe { ptr::read_volatile(&__isr_depth) } > 1;
+}
+
+///
+/// Tells the allocator whether the code running is the kernel's: boot code,
+/// or anything an interrupt or syscall has run. Processes run in ring 3,
+/// and can't read __isr_depth, so this goes by the privilege level.
+///
+pub fn in_kernel() -> bool {
+    return unsafe { __get_cs() } & x86arch::RPL_USER == 0;
+}
+
+///
+/// Gets the kernel context a nested interrupt broke into. Only meaningful
+/// when nested() is true.
+///
+pub fn kern_cxt() -> &'static mut pcbs::Context {
+    return unsafe { &mut *(KERN_CXT as *mut pcbs::Context) };
+}
+
 /// Initialize the interrupt table
 pub fn __init_interrupts() {
     INT.lock().init_idt();
@@ -0,0 +1,443 @@
+///
+/// kalloc.rs
+///
+/// Author: Jonathan Schenk
+///
+/// The kernel heap. Memory the BIOS says is usable is handed to a buddy
+/// allocator in power of two blocks from one page up to MAX_ORDER. Small
+/// requests are carved out of pages kept in per size class free lists so
+/// they don't each eat a page.
+///
+/// Every block is aligned to its own size (size classes to theirs, buddy
+/// blocks to theirs), so callers after alignment only need to ask for at
+/// least that many bytes.
+///
+/// _kmalloc, _kfree and _km_init are exported for the C and assembly code.
+///
+////////////////////////////////////////////////////////////////////////////////
+
+use spin::Mutex;
+use crate::frames;
+use crate::println;
+use crate::print;
+
+/// Everything the heap hands out is below this; it's all the boot page
+/// tables map
+pub const HEAP_LIMIT: u64 = 0x200000;
+
+/// Smallest buddy block, one page
+const MIN_ORDER: usize = 12;
+
+/// Biggest buddy block
+const MAX_ORDER: usize = 21;
+
+/// How many buddy orders there are
+pub const NUM_ORDERS: usize = MAX_ORDER - MIN_ORDER + 1;
+
+/// Smallest size class, big enough for the two free list links
+const MIN_CLASS: usize = 4;
+
+/// Size classes run from 1 << MIN_CLASS up to half a page
+const NUM_CLASSES: usize = MIN_ORDER - MIN_CLASS;
+
+const PAGE_SIZE: u64 = 1 << MIN_ORDER;
+const MAX_PAGES: usize = (HEAP_LIMIT / PAGE_SIZE) as usize;
+
+/// Words in a slab page's bitmap, enough for the smallest size class
+const SLAB_WORDS: usize = (PAGE_SIZE >> MIN_CLASS) as usize / 64;
+
+/// What a page's tag says about it; the low bits hold the order or class.
+/// Pages that aren't the first page of a block are tagged 0.
+const PG_FREE: u8 = 0x40; // first page of a free buddy block
+const PG_USED: u8 = 0x80; // first page of an allocated buddy block
+const PG_SLAB: u8 = 0xc0; // carved into size class objects
+const PG_KIND: u8 = 0xc0;
+const PG_SIZE: u8 = 0x3f;
+
+/// Free blocks and objects are kept in doubly linked lists threaded
+/// through the free memory itself
+struct Link {
+    next: u64,
+    prev: u64,
+}
+
+/// The heap's bookkeeping
+pub struct KHeap {
+    orders: [u64; NUM_ORDERS],      // free lists of buddy blocks
+    classes: [u64; NUM_CLASSES],    // free lists of size class objects
+    nblocks: [u64; NUM_ORDERS],     // length of each buddy free list
+    nobjs: [u64; NUM_CLASSES],      // length of each size class free list
+    tags: [u8; MAX_PAGES],          // PG_* tag of each page
+    live: [u16; MAX_PAGES],         // objects in use in each slab page
+    used: [[u64; SLAB_WORDS]; MAX_PAGES], // bit per object in use in each slab page
+    total: u64,                     // bytes handed to the heap at boot
+}
+
+/// What stats() reports
+pub struct KmemStats {
+    pub total: u64,                 // bytes the heap manages
+    pub free: u64,                  // bytes free, counting slab objects
+    pub largest: u64,               // biggest block kmalloc could return now
+    pub blocks: [u64; NUM_ORDERS],  // free buddy blocks of each order
+    pub slab_free: u64,             // bytes free in partly used slab pages
+}
+
+/// Smallest n with 1 << n >= size
+fn log2_up(size: u64) -> usize {
+    let mut n = 0;
+    while (1u64 << n) < size {
+        n += 1;
+    }
+    return n;
+}
+
+/// Page index of an address
+fn page(addr: u64) -> usize {
+    return (addr >> MIN_ORDER) as usize;
+}
+
+/// Word and bit of a slab object in its page's bitmap
+fn slot(obj: u64, class: usize) -> (usize, u64) {
+    let n = ((obj & (PAGE_SIZE - 1)) >> class) as usize;
+    return (n / 64, 1 << (n % 64));
+}
+
+impl KHeap {
+
+    /// Pushes block onto a free list
+    fn push(list: &mut u64, block: u64) {
+        let link = unsafe { &mut *(block as *mut Link) };
+        link.next = *list;
+        link.prev = 0;
+        if *list != 0 {
+            unsafe { (*(*list as *mut Link)).prev = block };
+        }
+        *list = block;
+    }
+
+    /// Takes block off a free list
+    fn unlink(list: &mut u64, block: u64) {
+        let link = unsafe { &mut *(block as *mut Link) };
+        if link.prev != 0 {
+            unsafe { (*(link.prev as *mut Link)).next = link.next };
+        }
+        else {
+            *list = link.next;
+        }
+        if link.next != 0 {
+            unsafe { (*(link.next as *mut Link)).prev = link.prev };
+        }
+    }
+
+    ///
+    /// release - gives a buddy block back, merging it with its buddy for
+    /// as long as the buddy is free too
+    ///
+    /// params:
+    ///     block: start of the block
+    ///     order: log2 of its size
+    ///
+    fn release(&mut self, block: u64, order: usize) {
+        let mut block = block;
+        let mut order = order;
+        while order < MAX_ORDER {
+            let buddy = block ^ (1 << order);
+            if page(buddy) >= MAX_PAGES ||
+                self.tags[page(buddy)] != PG_FREE | order as u8 {
+                break;
+            }
+            KHeap::unlink(&mut self.orders[order - MIN_ORDER], buddy);
+            self.nblocks[order - MIN_ORDER] -= 1;
+            self.tags[page(buddy)] = 0;
+            self.tags[page(block)] = 0;
+            block &= !(1 << order);
+            order += 1;
+        }
+        self.tags[page(block)] = PG_FREE | order as u8;
+        KHeap::push(&mut self.orders[order - MIN_ORDER], block);
+        self.nblocks[order - MIN_ORDER] += 1;
+    }
+
+    ///
+    /// buddy_alloc - takes a block off the buddy lists, splitting a bigger
+    /// one if there isn't one the right size
+    ///
+    /// param:
+    ///     order: log2 of the size wanted
+    ///
+    /// returns:
+    ///     the block, or 0 if there's nothing big enough
+    ///
+    fn buddy_alloc(&mut self, order: usize) -> u64 {
+        let mut o = order;
+        while o <= MAX_ORDER && self.orders[o - MIN_ORDER] == 0 {
+            o += 1;
+        }
+        if o > MAX_ORDER {
+            return 0;
+        }
+        let block = self.orders[o - MIN_ORDER];
+        KHeap::unlink(&mut self.orders[o - MIN_ORDER], block);
+        self.nblocks[o - MIN_ORDER] -= 1;
+
+        // Give back the top half until it's the size we want
+        while o > order {
+            o -= 1;
+            let half = block + (1 << o);
+            self.tags[page(half)] = PG_FREE | o as u8;
+            KHeap::push(&mut self.orders[o - MIN_ORDER], half);
+            self.nblocks[o - MIN_ORDER] += 1;
+        }
+        self.tags[page(block)] = PG_USED | order as u8;
+        return block;
+    }
+
+    ///
+    /// slab_alloc - takes an object off a size class list, carving up a
+    /// new page if the list is empty
+    ///
+    /// param:
+    ///     class: log2 of the object size
+    ///
+    /// returns:
+    ///     the object, or 0 if there's no page to carve
+    ///
+    fn slab_alloc(&mut self, class: usize) -> u64 {
+        let c = class - MIN_CLASS;
+        if self.classes[c] == 0 {
+            let pg = self.buddy_alloc(MIN_ORDER);
+            if pg == 0 {
+                return 0;
+            }
+            self.tags[page(pg)] = PG_SLAB | class as u8;
+            self.live[page(pg)] = 0;
+            self.used[page(pg)] = [0; SLAB_WORDS];
+            let mut obj = pg + PAGE_SIZE;
+            while obj > pg {
+                obj -= 1 << class;
+                KHeap::push(&mut self.classes[c], obj);
+                self.nobjs[c] += 1;
+            }
+        }
+        let obj = self.classes[c];
+        KHeap::unlink(&mut self.classes[c], obj);
+        self.nobjs[c] -= 1;
+        self.live[page(obj)] += 1;
+        let (w, bit) = slot(obj, class);
+        self.used[page(obj)][w] |= bit;
+        return obj;
+    }
+
+    ///
+    /// slab_free - puts an object back on its size class list. When the
+    /// last object in a page comes back, the page goes back to the buddy
+    /// allocator. Panics if the object is already free.
+    ///
+    /// params:
+    ///     obj: the object
+    ///     class: log2 of its size
+    ///
+    fn slab_free(&mut self, obj: u64, class: usize) {
+        let c  = class - MIN_CLASS;
+        let pg = obj & !(PAGE_SIZE - 1);
+        let (w, bit) = slot(obj, class);
+        if self.used[page(pg)][w] & bit == 0 {
+            panic!("kfree of {:#x}, which was already freed", obj);
+        }
+        self.used[page(pg)][w] &= !bit;
+        KHeap::push(&mut self.classes[c], obj);
+        self.nobjs[c] += 1;
+        self.live[page(pg)] -= 1;
+        if self.live[page(pg)] != 0 {
+            return;
+        }
+        let mut o = pg;
+        while o < pg + PAGE_SIZE {
+            KHeap::unlink(&mut self.classes[c], o);
+            self.nobjs[c] -= 1;
+            o += 1 << class;
+        }
+        self.tags[page(pg)] = 0;
+        self.release(pg, MIN_ORDER);
+    }
+
+    ///
+    /// alloc - allocates a block
+    ///
+    /// param:
+    ///     size: bytes wanted
+    ///
+    /// returns:
+    ///     the block, aligned to its size, or 0 if there's no memory
+    ///
+    pub fn alloc(&mut self, size: u64) -> u64 {
+        let order = log2_up(size);
+        if order < MIN_ORDER {
+            return self.slab_alloc(order.max(MIN_CLASS));
+        }
+        if order > MAX_ORDER {
+            return 0;
+        }
+        return self.buddy_alloc(order);
+    }
+
+    ///
+    /// free - gives a block back
+    ///
+    /// param:
+    ///     block: what alloc returned, or 0 for nothing
+    ///
+    /// Panics if block didn't come from alloc, or was already freed,
+    /// since either means the kernel's bookkeeping is broken.
+    ///
+    pub fn free(&mut self, block: u64) {
+        if block == 0 {
+            return;
+        }
+        let tag = if page(block) < MAX_PAGES { self.tags[page(block)] } else { 0 };
+        let size = (tag & PG_SIZE) as usize;
+        if tag & PG_KIND == PG_SLAB && block & ((1 << size) - 1) == 0 {
+            self.slab_free(block, size);
+        }
+        else if tag & PG_KIND == PG_USED && block & (PAGE_SIZE - 1) == 0 {
+            self.release(block, size);
+        }
+        else {
+            panic!("kfree of {:#x}, which isn't an allocated block", block);
+        }
+    }
+
+    ///
+    /// add_region - gives a range of memory to the heap
+    ///
+    /// params:
+    ///     base: first byte
+    ///     end: one past the last byte
+    ///
+    fn add_region(&mut self, base: u64, end: u64) {
+        let mut base = (base + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+        let end = end.min(HEAP_LIMIT) & !(PAGE_SIZE - 1);
+        while base < end {
+            // Biggest block that's aligned here and still fits
+            let mut order = MAX_ORDER;
+            while base & ((1 << order) - 1) != 0 || base + (1 << order) > end {
+                order -= 1;
+            }
+            self.release(base, order);
+            self.total += 1 << order;
+            base += 1 << order;
+        }
+    }
+
+    ///
+    /// stats - reports how much is free and how broken up it is
+    ///
+    pub fn stats(&self) -> KmemStats {
+        let mut st = KmemStats {
+            total: self.total,
+            free: 0,
+            largest: 0,
+            blocks: self.nblocks,
+            slab_free: 0,
+        };
+        for i in 0..NUM_ORDERS {
+            st.free += self.nblocks[i] << (i + MIN_ORDER);
+            if self.nblocks[i] != 0 {
+                st.largest = 1 << (i + MIN_ORDER);
+            }
+        }
+        for i in 0..NUM_CLASSES {
+            st.slab_free += self.nobjs[i] << (i + MIN_CLASS);
+        }
+        st.free += st.slab_free;
+        return st;
+    }
+}
+
+/// Our global kernel heap. Not lazy_static like the others: the slab
+/// bitmaps make it too big to build on the boot stack, so it's built at
+/// compile time instead.
+pub static KHEAP: Mutex<KHeap> = Mutex::new(KHeap {
+    orders: [0; NUM_ORDERS],
+    classes: [0; NUM_CLASSES],
+    nblocks: [0; NUM_ORDERS],
+    nobjs: [0; NUM_CLASSES],
+    tags: [0; MAX_PAGES],
+    live: [0; MAX_PAGES],
+    used: [[0; SLAB_WORDS]; MAX_PAGES],
+    total: 0,
+});
+
+///
+/// kmalloc - allocates kernel memory
+///
+/// param:
+///     size: bytes wanted
+///
+/// returns:
+///     the block, aligned to the power of two size it was rounded up to,
+///     or 0 if there's no memory
+///
+pub fn kmalloc(size: u64) -> u64 {
+    return KHEAP.lock().alloc(size);
+}
+
+///
+/// kfree - gives back memory from kmalloc
+///
+/// param:
+///     block: the block, or 0 for nothing
+///
+pub fn kfree(block: u64) {
+    KHEAP.lock().free(block);
+}
+
+/// Wraps kmalloc for C and assembly code
+#[no_mangle]
+pub extern "C" fn _kmalloc(size: u64) -> u64 {
+    return kmalloc(size);
+}
+
+/// Wraps kfree for C and assembly code
+#[no_mangle]
+pub extern "C" fn _kfree(block: u64) {
+    kfree(block);
+}
+
+///
+/// stats - reports on the kernel heap
+///
+pub fn stats() -> KmemStats {
+    return KHEAP.lock().stats();
+}
+
+///
+/// print_stats - prints the free memory and how it's broken up
+///
+pub fn print_stats() {
+    let st = stats();
+    print!("KMEM: {}K of {}K free, largest {}K, slabs {}K free, blocks",
+           st.free / 1024, st.total / 1024, st.largest / 1024, st.slab_free / 1024);
+    for i in 0..NUM_ORDERS {
+        if st.blocks[i] != 0 {
+            print!(" {}K:{}", (1u64 << (i + MIN_ORDER)) / 1024, st.blocks[i]);
+        }
+    }
+    println!();
+}
+
+///
+/// Builds the heap out of the usable regions in the BIOS memory map,
+/// leaving alone everything below the end of the kernel image. Runs
+/// before rs_sys_init, so it can't print.
+///
+#[no_mangle]
+pub extern "C" fn _km_init() {
+    let cutoff = frames::image_end();
+    let mut heap = KHEAP.lock();
+    frames::for_each_usable(|base, end| {
+        if end > cutoff && base < HEAP_LIMIT {
+            heap.add_region(base.max(cutoff), end);
+        }
+    });
+}
@@ -0,0 +1,78 @@
+///
+/// kheap.rs
+///
+/// Author: Jonathan Schenk
+///
+/// The allocator behind Box, Vec and friends. Kernel code and user code are
+/// linked into the same image and so share one #[global_allocator]; this
+/// one sends kernel allocations to the kernel heap and user allocations to
+/// the calling process' heap (ulibs::UserHeap).
+///
+////////////////////////////////////////////////////////////////////////////////
+
+use core::alloc::{GlobalAlloc, Layout};
+use crate::common;
+use crate::interrupt;
+use crate::kalloc;
+use crate::ulibs;
+use crate::uprintln;
+
+///
+/// The kernel heap, as a GlobalAlloc. kmalloc aligns every block to its
+/// size, so asking for at least align bytes is all alignment takes.
+///
+pub struct KernelHeap;
+
+unsafe impl GlobalAlloc for KernelHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let size = layout.size().max(layout.align());
+        return kalloc::kmalloc(size as u64) as *mut u8;
+    }
+
+    unsafe fn dealloc(&self, p: *mut u8, _layout: Layout) {
+        kalloc::kfree(p as u64);
+    }
+}
+
+///
+/// The #[global_allocator]. Picks a heap based on who's asking.
+///
+pub struct SplitHeap;
+
+unsafe impl GlobalAlloc for SplitHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if interrupt::in_kernel() {
+            return KernelHeap.alloc(layout);
+        }
+        return ulibs::UserHeap.alloc(layout);
+    }
+
+    unsafe fn dealloc(&self, p: *mut u8, layout: Layout) {
+        if interrupt::in_kernel() {
+            KernelHeap.dealloc(p, layout);
+        }
+        else {
+            ulibs::UserHeap.dealloc(p, layout);
+        }
+    }
+}
+
+///
+/// alloc_failed - what to do when an allocation can't be satisfied
+///
+/// A process that runs out of heap is terminated with EXIT_FAILURE; the
+/// kernel running out is fatal.
+///
+/// param:
+///     layout: what was asked for
+///
+pub fn alloc_failed(layout: Layout) -> ! {
+    if !interrupt::in_kernel() {
+        uprintln!("pid {}: out of heap memory allocating {} bytes (align {})",
+                  ulibs::sys_pid(), layout.size(), layout.align());
+        ulibs::sys_exit(common::EXIT_FAILURE);
+        loop {}
+    }
+    panic!("kernel heap exhausted allocating {} bytes (align {})",
+           layout.size(), layout.align());
+}
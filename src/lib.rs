@@ -12,10 +12,12 @@
 #![feature(const_fn)]
 #![feature(asm)]
 #![feature(const_raw_ptr_deref)]
+#![feature(alloc_error_handler)]
 
 #[macro_use]
 extern crate lazy_static;
 extern crate spin;
+extern crate alloc;
 
 mod c_io;
 mod interrupt;
@@ -26,10 +28,26 @@ mod pcbs;
 mod scheduler;
 mod stacks;
 mod users;
+mod programs;
+mod files;
+mod vma;
+mod kalloc;
+mod frames;
+mod paging;
+mod space;
+mod gdt;
+mod kheap;
 mod ulibs;
 mod syscalls;
+mod uaccess;
+mod fault;
 
 use core::panic::PanicInfo;
+use core::alloc::Layout;
+
+/// Kernel code gets the kernel heap, user programs their sys_brk heap
+#[global_allocator]
+static ALLOCATOR: kheap::SplitHeap = kheap::SplitHeap;
 
 extern "C" {
     #[no_mangle]
@@ -46,17 +64,32 @@ pub extern fn rs_sys_init() {
     c_io::WRITER.lock().c_puts("System init starting\n");
     c_io::WRITER.lock().c_puts("--------------------\n");
     c_io::WRITER.lock().c_puts("Modules:\n");
+    kalloc::print_stats();
+    frames::_frames_init();
+    paging::_paging_init();
+    frames::_frames_refs_init();
+    gdt::_gdt_init();
     interrupt::__init_interrupts();
+    fault::_fault_init();
     clock::_clk_init();
-    stacks::_stk_init();
     scheduler::_scheduler_init();
     syscalls::_syscall_init();
-    let entry = (users::init as *mut fn()->i32) as u64;
+    programs::_programs_init();
+    files::_files_init();
+    let init = programs::find(b"init").expect("no init program");
+    let mut args = stacks::Args::new();
+    args.push_arg(b"init");
     let stk_addr = stacks::stk_alloc();
+    let mut space = space::new(None).expect("no memory for init's address space");
+    let page = space::stack_top(&mut space);
     let stk = unsafe { &mut *(stk_addr as *mut stacks::StkBuffer) };
     //println!("stk_addr {:x}", stk_addr);
-    let cxt = stacks::_stk_setup(stk, entry);
-    scheduler::SCHED.lock()._add_proc(cxt, stk_addr, 0, 0, pcbs::PID_INIT, pcbs::PID_INIT, 0);
+    let cxt = stacks::_stk_setup(stk, page, init.main as u64, &args);
+    let spot = scheduler::SCHED.lock()._add_proc(cxt, stk_addr, 0, 0, pcbs::PID_INIT, pcbs::PID_INIT, 0, space);
+    let pcb = unsafe { &mut *(scheduler::SCHED.lock().get_proc(spot as i8) as *mut pcbs::Pcb) };
+    pcb.prio = init.prio;
+    pcb.pgid = pcbs::PID_INIT;
+    pcb.fds  = files::std_fds();
     scheduler::SCHED.lock()._schedule(0);
     scheduler::SCHED.lock()._dispatch();
     //scheduler::SCHED.lock().dump_curr();
@@ -67,6 +100,12 @@ pub extern fn rs_sys_init() {
 #[no_mangle]
 pub extern fn eh_personality() {}
 
+/// What happens when the heap can't grow any more
+#[alloc_error_handler]
+fn alloc_error(layout: Layout) -> ! {
+    kheap::alloc_failed(layout);
+}
+
 /// A panic handler that I don't know how to invoke
 #[panic_handler]
 #[no_mangle]
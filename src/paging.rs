@@ -0,0 +1,763 @@
+///
+/// paging.rs
+///
+/// Author: Jonathan Schenk
+///
+/// Four level page tables. long_mode.S identity maps the first 2M just to
+/// get us into long mode; at boot we build the kernel's own tables, which
+/// identity map all of RAM with the kernel's sections protected, and
+/// switch to them.
+///
+/// Page tables come out of the kernel heap, which is identity mapped, so a
+/// table's physical address is also where the kernel can get at it. So
+/// does any frame from frames.rs, which is how the kernel fills in and
+/// copies the pages processes get (see map_frames and share_cow).
+///
+////////////////////////////////////////////////////////////////////////////////
+
+use core::ptr;
+use spin::Mutex;
+use lazy_static::lazy_static;
+use crate::common;
+use crate::frames;
+use crate::kalloc;
+use crate::x86arch;
+use crate::println;
+
+extern "C" {
+    #[no_mangle]
+    fn __get_cr0() -> u64;
+    #[no_mangle]
+    fn __set_cr0(value:u64);
+    #[no_mangle]
+    fn __get_cr3() -> u64;
+    #[no_mangle]
+    fn __set_cr3(root:u64);
+    #[no_mangle]
+    fn __invlpg(addr:u64);
+    #[no_mangle]
+    fn __rdmsr(msr:u32) -> u64;
+    #[no_mangle]
+    fn __wrmsr(msr:u32, value:u64);
+    #[no_mangle]
+    static __image_start: u8;
+    #[no_mangle]
+    static __text_end: u8;
+    #[no_mangle]
+    static __rodata_end: u8;
+}
+
+/// Size of a page
+pub const PAGE_SIZE: u64 = 4096;
+
+/// Size of a huge (page directory level) page
+pub const HUGE_SIZE: u64 = 0x200000;
+
+/// Page table entry bits
+pub const PG_PRESENT: u64 = 0x001;
+pub const PG_WRITE: u64   = 0x002;
+pub const PG_USER: u64    = 0x004;
+pub const PG_PWT: u64     = 0x008;
+pub const PG_PCD: u64     = 0x010;
+pub const PG_ACCESSED: u64 = 0x020;
+pub const PG_DIRTY: u64   = 0x040;
+pub const PG_HUGE: u64    = 0x080;
+pub const PG_GLOBAL: u64  = 0x100;
+pub const PG_GUARD: u64   = 0x200; // ours: not present on purpose, see guard()
+pub const PG_COW: u64     = 0x400; // ours: writable, but shared until written
+pub const PG_NONE: u64    = 0x800; // ours: not present, but the frame is kept
+pub const PG_NX: u64      = 1 << 63;
+
+/// Where the address lives in an entry
+const ADDR_MASK: u64 = 0x000f_ffff_ffff_f000;
+
+/// Entries per table
+const ENTRIES: usize = 512;
+
+/// Levels, named after the table at each one
+const PML4: usize = 4;
+const PDPT: usize = 3;
+const PD: usize = 2;
+const PT: usize = 1;
+
+/// Video memory and the BIOS ROMs
+const VGA_START: u64 = 0xa0000;
+const VGA_END: u64 = 0x100000;
+
+/// Index into the table at level for virt
+fn index(virt: u64, level: usize) -> usize {
+    return ((virt >> (12 + 9 * (level - 1))) & 0x1ff) as usize;
+}
+
+/// A zeroed page for a table, or 0 if there isn't one
+fn table_alloc() -> u64 {
+    let t = kalloc::kmalloc(PAGE_SIZE);
+    if t != 0 {
+        unsafe { ptr::write_bytes(t as *mut u8, 0, PAGE_SIZE as usize) };
+    }
+    return t;
+}
+
+/// The entries of the table at phys
+fn table(phys: u64) -> &'static mut [u64; ENTRIES] {
+    return unsafe { &mut *(phys as *mut [u64; ENTRIES]) };
+}
+
+/// One address space
+pub struct PageTable {
+    root: u64, // physical address of the PML4
+}
+
+impl PageTable {
+
+    ///
+    /// new - makes an empty address space
+    ///
+    /// returns:
+    ///     the address space, or None if there's no memory for a root
+    ///
+    pub fn new() -> Option<PageTable> {
+        let root = table_alloc();
+        if root == 0 {
+            return None;
+        }
+        return Some(PageTable { root: root });
+    }
+
+    ///
+    /// new_user - makes an address space for a process. The kernel's
+    /// top level entries are shared, so the kernel is mapped the same way
+    /// in every process and the rest is the process' own.
+    ///
+    /// returns:
+    ///     the address space, or None if there's no memory for a root
+    ///
+    pub fn new_user() -> Option<PageTable> {
+        let pt = PageTable::new()?;
+        let kern = KERNEL.lock().root;
+        table(pt.root).copy_from_slice(table(kern));
+        return Some(pt);
+    }
+
+    /// An address space with nothing in it, not even a root
+    pub const fn none() -> PageTable {
+        return PageTable { root: 0 };
+    }
+
+    /// Physical address of the root, for CR3
+    pub fn root(&self) -> u64 {
+        return self.root;
+    }
+
+    ///
+    /// activate - switches the CPU to this address space
+    ///
+    pub fn activate(&self) {
+        if self.root != 0 && unsafe { __get_cr3() } & ADDR_MASK != self.root {
+            unsafe { __set_cr3(self.root) };
+        }
+    }
+
+    ///
+    /// destroy - frees a process' page tables. The memory they mapped
+    /// belongs to whoever mapped it and isn't touched. If this address
+    /// space is in use the CPU goes back to the kernel's first, since the
+    /// tables are about to be reused.
+    ///
+    pub fn destroy(&mut self) {
+        if self.root == 0 {
+            return;
+        }
+        let kern = KERNEL.lock().root;
+        if unsafe { __get_cr3() } & ADDR_MASK == self.root {
+            unsafe { __set_cr3(kern) };
+        }
+        let top = table(self.root);
+        for i in 0..ENTRIES {
+            if top[i] & PG_PRESENT != 0 && top[i] != table(kern)[i] {
+                free_table(top[i] & ADDR_MASK, PDPT);
+            }
+        }
+        kalloc::kfree(self.root);
+        self.root = 0;
+    }
+
+    /// Drops virt from the TLB if this is the address space in use. The
+    /// kernel's part (top level entry 0) is in every address space, so a
+    /// change there gets dropped whichever one is loaded.
+    fn flush(&self, virt: u64) {
+        if index(virt, PML4) == 0 || unsafe { __get_cr3() } & ADDR_MASK == self.root {
+            unsafe { __invlpg(virt) };
+        }
+    }
+
+    ///
+    /// walk - finds the entry for virt at a given level
+    ///
+    /// params:
+    ///     virt: the address
+    ///     level: PT for a page's entry, PD for a huge page's
+    ///     create: fill in missing tables and split huge pages on the way
+    ///
+    /// returns:
+    ///     the entry, or None if a table is missing (or couldn't be made)
+    ///
+    fn walk(&self, virt: u64, level: usize, create: bool) -> Option<&'static mut u64> {
+        let mut t = self.root;
+        let mut l = PML4;
+        loop {
+            let ent = &mut table(t)[index(virt, l)];
+            if l == level {
+                return Some(ent);
+            }
+            if *ent & PG_PRESENT == 0 {
+                if !create {
+                    return None;
+                }
+                let new = table_alloc();
+                if new == 0 {
+                    return None;
+                }
+                *ent = new | PG_PRESENT | PG_WRITE | PG_USER;
+            }
+            else if *ent & PG_HUGE != 0 {
+                if !create || !split(ent, l) {
+                    return None;
+                }
+            }
+            t = *ent & ADDR_MASK;
+            l -= 1;
+        }
+    }
+
+    ///
+    /// map - maps one page
+    ///
+    /// params:
+    ///     virt: virtual address, page aligned
+    ///     phys: physical address, page aligned
+    ///     flags: PG_* bits; PG_PRESENT is added
+    ///
+    /// returns:
+    ///     E_SUCCESS, E_BAD_ARGS or E_NO_MEM
+    ///
+    pub fn map(&mut self, virt: u64, phys: u64, flags: u64) -> i64 {
+        if virt % PAGE_SIZE != 0 || phys % PAGE_SIZE != 0 {
+            return common::E_BAD_ARGS;
+        }
+        let ent = match self.walk(virt, PT, true) {
+            Some(ent) => ent,
+            None => return common::E_NO_MEM,
+        };
+        *ent = phys | (flags & !PG_HUGE) | PG_PRESENT;
+        self.flush(virt);
+        return common::E_SUCCESS;
+    }
+
+    ///
+    /// map_range - maps physically contiguous memory a page at a time
+    ///
+    /// params:
+    ///     virt: virtual address, page aligned
+    ///     phys: physical address, page aligned
+    ///     len: bytes to map; rounded up to whole pages
+    ///     flags: PG_* bits
+    ///
+    /// returns:
+    ///     E_SUCCESS, E_BAD_ARGS or E_NO_MEM; on failure nothing is mapped
+    ///
+    pub fn map_range(&mut self, virt: u64, phys: u64, len: u64, flags: u64) -> i64 {
+        let mut off = 0;
+        while off < len {
+            let ret = self.map(virt + off, phys + off, flags);
+            if ret != common::E_SUCCESS {
+                self.unmap_range(virt, off);
+                return ret;
+            }
+            off += PAGE_SIZE;
+        }
+        return common::E_SUCCESS;
+    }
+
+    ///
+    /// unmap_range - unmaps every page in a range
+    ///
+    /// params:
+    ///     virt: virtual address, page aligned
+    ///     len: bytes to unmap; rounded up to whole pages
+    ///
+    pub fn unmap_range(&mut self, virt: u64, len: u64) {
+        let mut off = 0;
+        while off < len {
+            self.unmap(virt + off);
+            off += PAGE_SIZE;
+        }
+    }
+
+    ///
+    /// map_huge - maps one 2M page
+    ///
+    /// params:
+    ///     virt: virtual address, 2M aligned
+    ///     phys: physical address, 2M aligned
+    ///     flags: PG_* bits; PG_PRESENT and PG_HUGE are added
+    ///
+    /// returns:
+    ///     E_SUCCESS, E_BAD_ARGS or E_NO_MEM
+    ///
+    pub fn map_huge(&mut self, virt: u64, phys: u64, flags: u64) -> i64 {
+        if virt % HUGE_SIZE != 0 || phys % HUGE_SIZE != 0 {
+            return common::E_BAD_ARGS;
+        }
+        let ent = match self.walk(virt, PD, true) {
+            Some(ent) => ent,
+            None => return common::E_NO_MEM,
+        };
+        // Whatever small pages were here go away with their table
+        if *ent & (PG_PRESENT | PG_HUGE) == PG_PRESENT {
+            kalloc::kfree(*ent & ADDR_MASK);
+        }
+        *ent = phys | flags | PG_PRESENT | PG_HUGE;
+        for v in (virt..virt + HUGE_SIZE).step_by(PAGE_SIZE as usize) {
+            self.flush(v);
+        }
+        return common::E_SUCCESS;
+    }
+
+    ///
+    /// unmap - unmaps one page. A page inside a huge page gets the huge
+    /// page split around it first.
+    ///
+    /// param:
+    ///     virt: virtual address of the page
+    ///
+    /// returns:
+    ///     the physical address it was mapped to, or None if it wasn't
+    ///
+    pub fn unmap(&mut self, virt: u64) -> Option<u64> {
+        let virt = virt & !(PAGE_SIZE - 1);
+        if self.translate(virt).is_none() {
+            return None;
+        }
+        let ent = self.walk(virt, PT, true)?;
+        let phys = *ent & ADDR_MASK;
+        *ent = 0;
+        self.flush(virt);
+        return Some(phys);
+    }
+
+    ///
+    /// translate - looks up where a virtual address goes
+    ///
+    /// param:
+    ///     virt: the address
+    ///
+    /// returns:
+    ///     the physical address, or None if virt isn't mapped
+    ///
+    pub fn translate(&self, virt: u64) -> Option<u64> {
+        let mut t = self.root;
+        let mut l = PML4;
+        loop {
+            let ent = table(t)[index(virt, l)];
+            if ent & PG_PRESENT == 0 {
+                return None;
+            }
+            if l == PT || ent & PG_HUGE != 0 {
+                let size = 1u64 << (12 + 9 * (l - 1));
+                return Some((ent & ADDR_MASK & !(size - 1)) + (virt & (size - 1)));
+            }
+            t = ent & ADDR_MASK;
+            l -= 1;
+        }
+    }
+
+    ///
+    /// map_frames - backs a range with fresh zeroed frames
+    ///
+    /// params:
+    ///     virt: virtual address, page aligned
+    ///     len: bytes to map; rounded up to whole pages
+    ///     flags: PG_* bits
+    ///
+    /// returns:
+    ///     E_SUCCESS, E_BAD_ARGS or E_NO_MEM; on failure nothing is mapped
+    ///
+    pub fn map_frames(&mut self, virt: u64, len: u64, flags: u64) -> i64 {
+        let mut off = 0;
+        while off < len {
+            let frame = frames::frame_alloc();
+            let ret = if frame == 0 {
+                common::E_NO_MEM
+            }
+            else {
+                unsafe { ptr::write_bytes(frame as *mut u8, 0, PAGE_SIZE as usize) };
+                self.map(virt + off, frame, flags)
+            };
+            if ret != common::E_SUCCESS {
+                if frame != 0 {
+                    frames::frame_free(frame);
+                }
+                self.unmap_frames(virt, off);
+                return ret;
+            }
+            off += PAGE_SIZE;
+        }
+        return common::E_SUCCESS;
+    }
+
+    ///
+    /// unmap_frames - unmaps a range from map_frames or share_cow and
+    /// drops its references to the frames
+    ///
+    /// params:
+    ///     virt: virtual address, page aligned
+    ///     len: bytes to unmap; rounded up to whole pages
+    ///
+    pub fn unmap_frames(&mut self, virt: u64, len: u64) {
+        let mut off = 0;
+        while off < len {
+            if let Some(ent) = self.walk(virt + off, PT, false) {
+                if *ent & (PG_PRESENT | PG_NONE) != 0 {
+                    frames::frame_free(*ent & ADDR_MASK);
+                    *ent = 0;
+                    self.flush(virt + off);
+                }
+            }
+            off += PAGE_SIZE;
+        }
+    }
+
+    ///
+    /// protect_range - changes how a range from map_frames is mapped,
+    /// keeping its frames. Shared frames don't get made writable; they're
+    /// marked copy-on-write instead.
+    ///
+    /// params:
+    ///     virt: virtual address, page aligned
+    ///     len: bytes to change; rounded up to whole pages
+    ///     flags: PG_* bits, or PG_NONE for no access at all
+    ///
+    pub fn protect_range(&mut self, virt: u64, len: u64, flags: u64) {
+        let mut off = 0;
+        while off < len {
+            if let Some(ent) = self.walk(virt + off, PT, false) {
+                if *ent & (PG_PRESENT | PG_NONE) != 0 {
+                    let phys = *ent & ADDR_MASK;
+                    *ent = if flags & PG_NONE != 0 {
+                        phys | PG_NONE
+                    }
+                    else if flags & PG_WRITE != 0 && frames::frame_refs(phys) > 1 {
+                        phys | (flags & !PG_WRITE) | PG_COW | PG_PRESENT
+                    }
+                    else {
+                        phys | flags | PG_PRESENT
+                    };
+                    self.flush(virt + off);
+                }
+            }
+            off += PAGE_SIZE;
+        }
+    }
+
+    ///
+    /// share_cow - maps a range from map_frames into another address
+    /// space, sharing the frames. Writable pages become read-only and
+    /// copy-on-write in both, so whichever side writes first gets its own
+    /// copy (see copy_on_write). Nothing is copied here.
+    ///
+    /// params:
+    ///     child: the address space to share with
+    ///     virt: virtual address, page aligned
+    ///     len: bytes to share; rounded up to whole pages
+    ///
+    /// returns:
+    ///     E_SUCCESS or E_NO_MEM; on failure child may have part of the
+    ///     range, which unmap_frames cleans up
+    ///
+    pub fn share_cow(&self, child: &mut PageTable, virt: u64, len: u64) -> i64 {
+        let mut off = 0;
+        while off < len {
+            if let Some(ent) = self.walk(virt + off, PT, false) {
+                if *ent & (PG_PRESENT | PG_NONE) != 0 {
+                    if *ent & PG_WRITE != 0 {
+                        *ent = (*ent & !PG_WRITE) | PG_COW;
+                        self.flush(virt + off);
+                    }
+                    let cent = match child.walk(virt + off, PT, true) {
+                        Some(cent) => cent,
+                        None => return common::E_NO_MEM,
+                    };
+                    frames::frame_share(*ent & ADDR_MASK);
+                    *cent = *ent;
+                    child.flush(virt + off);
+                }
+            }
+            off += PAGE_SIZE;
+        }
+        return common::E_SUCCESS;
+    }
+
+    ///
+    /// copy_on_write - handles a write to a copy-on-write page. If some
+    /// other address space still shares the frame the page gets a copy of
+    /// its own; if not, it just becomes writable again.
+    ///
+    /// param:
+    ///     virt: the address written to
+    ///
+    /// returns:
+    ///     true if the write can be retried, false if virt isn't a
+    ///     copy-on-write page or there's no frame for the copy
+    ///
+    pub fn copy_on_write(&mut self, virt: u64) -> bool {
+        let virt = virt & !(PAGE_SIZE - 1);
+        let ent = match self.walk(virt, PT, false) {
+            Some(ent) => ent,
+            None => return false,
+        };
+        if *ent & (PG_PRESENT | PG_COW) != PG_PRESENT | PG_COW {
+            return false;
+        }
+        let old   = *ent & ADDR_MASK;
+        let flags = (*ent & !ADDR_MASK & !PG_COW) | PG_WRITE;
+        if frames::frame_refs(old) == 1 {
+            *ent = old | flags;
+        }
+        else {
+            let new = frames::frame_alloc();
+            if new == 0 {
+                return false;
+            }
+            unsafe {
+                ptr::copy_nonoverlapping(old as *const u8, new as *mut u8, PAGE_SIZE as usize);
+            }
+            *ent = new | flags;
+            frames::frame_free(old);
+        }
+        self.flush(virt);
+        return true;
+    }
+
+    ///
+    /// entry - gets the flags a page is mapped with
+    ///
+    /// param:
+    ///     virt: an address in the page
+    ///
+    /// returns:
+    ///     the PG_* bits of its entry, or 0 if it has none
+    ///
+    pub fn entry(&self, virt: u64) -> u64 {
+        let mut t = self.root;
+        let mut l = PML4;
+        loop {
+            let ent = table(t)[index(virt, l)];
+            if l == PT || ent & PG_HUGE != 0 || ent & PG_PRESENT == 0 {
+                return ent & !ADDR_MASK;
+            }
+            t = ent & ADDR_MASK;
+            l -= 1;
+        }
+    }
+
+    ///
+    /// guard - makes a page a guard page: unmapped, but marked so a fault
+    /// on it can be told apart from a wild pointer
+    ///
+    /// param:
+    ///     virt: virtual address of the page
+    ///
+    /// returns:
+    ///     E_SUCCESS or E_NO_MEM
+    ///
+    pub fn guard(&mut self, virt: u64) -> i64 {
+        let virt = virt & !(PAGE_SIZE - 1);
+        let ent = match self.walk(virt, PT, true) {
+            Some(ent) => ent,
+            None => return common::E_NO_MEM,
+        };
+        *ent = PG_GUARD;
+        self.flush(virt);
+        return common::E_SUCCESS;
+    }
+
+    ///
+    /// is_guard - checks whether an address is in a guard page
+    ///
+    pub fn is_guard(&self, virt: u64) -> bool {
+        return self.entry(virt) & (PG_PRESENT | PG_GUARD) == PG_GUARD;
+    }
+}
+
+///
+/// split - turns a huge page entry into a table of small pages that map
+/// the same memory the same way
+///
+/// params:
+///     ent: the entry
+///     level: the level it's at; only PD entries can be split
+///
+/// returns:
+///     false if it couldn't be split
+///
+fn split(ent: &mut u64, level: usize) -> bool {
+    if level != PD {
+        return false;
+    }
+    let t = table_alloc();
+    if t == 0 {
+        return false;
+    }
+    let base  = *ent & ADDR_MASK & !(HUGE_SIZE - 1);
+    let flags = *ent & !ADDR_MASK & !PG_HUGE;
+    let small = table(t);
+    for i in 0..ENTRIES {
+        small[i] = (base + i as u64 * PAGE_SIZE) | flags;
+    }
+    *ent = t | PG_PRESENT | PG_WRITE | PG_USER;
+    return true;
+}
+
+/// Frees the table at phys, which sits at level, and every table below it
+fn free_table(phys: u64, level: usize) {
+    if level > PT {
+        for ent in table(phys).iter() {
+            if *ent & (PG_PRESENT | PG_HUGE) == PG_PRESENT {
+                free_table(*ent & ADDR_MASK, level - 1);
+            }
+        }
+    }
+    kalloc::kfree(phys);
+}
+
+/// The kernel's address space
+lazy_static! {
+    pub static ref KERNEL: Mutex<PageTable> = Mutex::new(PageTable { root: 0 });
+}
+
+///
+/// map - maps a page in the kernel's address space
+///
+/// params:
+///     virt: virtual address, page aligned
+///     phys: physical address, page aligned
+///     flags: PG_* bits
+///
+/// returns:
+///     E_SUCCESS, E_BAD_ARGS or E_NO_MEM
+///
+pub fn map(virt: u64, phys: u64, flags: u64) -> i64 {
+    return KERNEL.lock().map(virt, phys, flags);
+}
+
+///
+/// unmap - unmaps a page from the kernel's address space
+///
+/// param:
+///     virt: virtual address of the page
+///
+/// returns:
+///     the physical address it was mapped to, or None if it wasn't
+///
+pub fn unmap(virt: u64) -> Option<u64> {
+    return KERNEL.lock().unmap(virt);
+}
+
+///
+/// translate - looks up an address in the kernel's address space
+///
+/// param:
+///     virt: the address
+///
+/// returns:
+///     the physical address, or None if virt isn't mapped
+///
+pub fn translate(virt: u64) -> Option<u64> {
+    return KERNEL.lock().translate(virt);
+}
+
+///
+/// guard - makes a page of the kernel's address space a guard page
+///
+/// param:
+///     virt: virtual address of the page
+///
+/// returns:
+///     E_SUCCESS or E_NO_MEM
+///
+pub fn guard(virt: u64) -> i64 {
+    return KERNEL.lock().guard(virt);
+}
+
+///
+/// cow_fault - handles a write fault on a copy-on-write page of the
+/// address space in use
+///
+/// param:
+///     virt: the faulting address, from CR2
+///
+/// returns:
+///     true if the write can be retried
+///
+pub fn cow_fault(virt: u64) -> bool {
+    let mut pt = PageTable { root: unsafe { __get_cr3() } & ADDR_MASK };
+    return pt.copy_on_write(virt);
+}
+
+///
+/// Builds the kernel's page tables and switches to them. Page 0 is a
+/// guard page to catch null pointers. The first 2M is mapped a page at a
+/// time so the image's sections can get the right protection; the rest
+/// of RAM gets huge pages. User programs are linked into the image, so
+/// its text and rodata are open to user mode too; nothing else is. The
+/// linker script puts .data.rel.ro (constants holding pointers, like
+/// program tables and string slices) in with the rodata.
+///
+pub fn _paging_init() {
+    let text    = unsafe { &__image_start as *const u8 } as u64;
+    let rodata  = unsafe { &__text_end as *const u8 } as u64;
+    let data    = unsafe { &__rodata_end as *const u8 } as u64;
+    let top     = (frames::FRAMES.lock().top() + HUGE_SIZE - 1) & !(HUGE_SIZE - 1);
+
+    let mut kern = match PageTable::new() {
+        Some(pt) => pt,
+        None => panic!("no memory for the kernel page tables"),
+    };
+
+    let mut ok = kern.guard(0) == common::E_SUCCESS;
+    for virt in (PAGE_SIZE..kalloc::HEAP_LIMIT).step_by(PAGE_SIZE as usize) {
+        let flags = if virt >= text && virt < rodata {
+            PG_USER
+        }
+        else if virt >= rodata && virt < data {
+            PG_USER | PG_NX
+        }
+        else if virt >= VGA_START && virt < VGA_END {
+            PG_WRITE | PG_PCD | PG_NX
+        }
+        else {
+            PG_WRITE | PG_NX
+        };
+        ok = ok && kern.map(virt, virt, flags) == common::E_SUCCESS;
+    }
+    for virt in (kalloc::HEAP_LIMIT..top).step_by(HUGE_SIZE as usize) {
+        ok = ok && kern.map_huge(virt, virt, PG_WRITE | PG_NX) == common::E_SUCCESS;
+    }
+    if !ok {
+        panic!("no memory for the kernel page tables");
+    }
+
+    // NX bits are reserved until NXE is on, and without WP the kernel
+    // could still write to read-only pages
+    unsafe {
+        let efer = __rdmsr(x86arch::MSR_EFER);
+        __wrmsr(x86arch::MSR_EFER, efer | x86arch::EFER_NXE);
+        __set_cr0(__get_cr0() | x86arch::CR0_WP);
+        __set_cr3(kern.root());
+    }
+    *KERNEL.lock() = kern;
+
+    println!("PAGING: {}K mapped, text {:#x}-{:#x} r-x, rodata to {:#x} r--",
+             top / 1024, text, rodata, data);
+}
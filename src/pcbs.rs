@@ -16,6 +16,9 @@ use crate::x86arch;
 use crate::common;
 use crate::c_io;
 use crate::stacks::StkBuffer;
+use crate::files;
+use crate::vma;
+use crate::paging;
 
 /// This should be an enum of process states, but Rust's enum comparison
 /// stuff is bad.
@@ -76,8 +79,10 @@ pub struct Context {
 #[no_mangle]
 #[repr(C)]
 pub struct Pcb {
-    pub cxt: &'static mut Context,     // context pointer
-    pub stack: &'static mut StkBuffer, // stack
+    pub cxt: *mut Context,              // context pointer, on the kernel stack
+    pub kstack: u64,                    // kernel stack (stacks::stk_alloc)
+    pub ksp: u64,                       // where it blocked on kstack, 0 if it didn't
+    pub space: paging::PageTable,      // address space, loaded into CR3 on dispatch
 
     pub event: u32,      // event for things like sleep
     pub exitstatus: u32, // How did we exit?
@@ -89,6 +94,55 @@ pub struct Pcb {
     pub state: u8,  // process state
     pub ticks: u8,  // remaining quantum
     pub spot: i8,   // Index in active queue
+    pub trace: u8,  // syscall trace flags (common::TRACE_*)
+    pub prio: u8,   // priority (common::PRIO_*), sets the quantum
+    pub pgid: u16,  // process group
+    pub fds: files::FdTable, // open files
+    pub heap: u64,  // base of the heap (space::HEAP_BASE), 0 until the first brk
+    pub brk: u64,   // program break; the heap is [heap, brk)
+    pub stack_bottom: u64, // lowest mapped address of the stack (space::STACK_BASE to start)
+    pub stack_limit: u64,  // most bytes the stack can grow to, at most space::STACK_MAX
+    pub vmas: vma::VmaTable, // regions from sys_mmap
+    pub sysmask: u64,  // bit n set if syscall n is allowed
+    pub syspolicy: u8, // what a denied syscall does (common::SYSPOL_*)
+}
+
+/// The raw pointers point into the process' kernel stack, which only the
+/// scheduler hands out, under its lock
+unsafe impl Send for Pcb {}
+
+impl Pcb {
+    /// An unused process slot
+    pub const EMPTY: Pcb = Pcb {
+        cxt: ptr::null_mut(),
+        kstack: 0,
+        ksp: 0,
+        space: paging::PageTable::none(),
+        event: 0,
+        exitstatus: 0,
+        pid: 0,
+        ppid: 0,
+        children: 0,
+        state: ST_UNUSED,
+        ticks: 0,
+        spot: 0,
+        trace: 0,
+        prio: common::PRIO_STD,
+        pgid: 0,
+        fds: [None; files::MAX_FDS],
+        heap: 0,
+        brk: 0,
+        stack_bottom: 0,
+        stack_limit: 0,
+        vmas: [None; vma::MAX_VMAS],
+        sysmask: 0,
+        syspolicy: 0,
+    };
+
+    /// The process' saved context. Only good once the slot is in use.
+    pub fn cxt(&self) -> &'static mut Context {
+        return unsafe { &mut *self.cxt };
+    }
 }
 
 /// Global PID getter
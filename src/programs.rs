@@ -0,0 +1,183 @@
+///
+/// programs.rs
+///
+/// Author: Jonathan Schenk
+///
+/// Registry of the user programs built into the kernel. Modules register
+/// their programs by name at boot, and exec/spawn find them here.
+///
+////////////////////////////////////////////////////////////////////////////////
+
+use spin::Mutex;
+use lazy_static::lazy_static;
+use crate::common;
+use crate::users;
+use crate::println;
+use crate::print;
+
+/// How many programs the registry holds
+pub const MAX_PROGRAMS: usize = 32;
+
+/// What a user program's main looks like
+pub type Main = extern "C" fn(argc:u64, argv:*const *const u8, envp:*const *const u8) -> i32;
+
+/// One registered program
+#[derive(Clone, Copy)]
+pub struct Program {
+    pub name: &'static str, // what exec and spawn call it
+    pub desc: &'static str, // one line about what it does
+    pub main: Main,         // entry point
+    pub prio: u8,           // priority it runs at (common::PRIO_*)
+}
+
+/// Program registry
+pub struct ProgTbl {
+    progs: [Option<Program>; MAX_PROGRAMS],
+    count: usize,
+}
+
+impl ProgTbl {
+    ///
+    /// register - adds a program to the registry
+    ///
+    /// param:
+    ///     prog: the program to add
+    ///
+    /// Panics if the registry is full, the name is taken or the priority
+    /// doesn't exist, since that can only be a mistake in the kernel.
+    ///
+    pub fn register(&mut self, prog: Program) {
+        if prog.name.len() >= common::MAX_PROG_NAME {
+            panic!("program name {} is too long", prog.name);
+        }
+        if prog.prio >= common::NUM_PRIOS {
+            panic!("program {} has bad priority {}", prog.name, prog.prio);
+        }
+        if self.find(prog.name.as_bytes()).is_some() {
+            panic!("program {} registered twice", prog.name);
+        }
+        if self.count >= MAX_PROGRAMS {
+            panic!("no room to register program {}", prog.name);
+        }
+        self.progs[self.count] = Some(prog);
+        self.count += 1;
+    }
+
+    ///
+    /// find - looks up a program by name
+    ///
+    /// param:
+    ///     name: the program's name
+    ///
+    /// returns:
+    ///     a copy of the program's entry, or None if there's no such program
+    ///
+    pub fn find(&self, name: &[u8]) -> Option<Program> {
+        for i in 0..self.count {
+            if let Some(prog) = self.progs[i] {
+                if prog.name.as_bytes() == name {
+                    return Some(prog);
+                }
+            }
+        }
+        return None;
+    }
+
+    ///
+    /// get - gets a program by its place in the registry
+    ///
+    /// param:
+    ///     ind: index, in registration order
+    ///
+    /// returns:
+    ///     a copy of the program's entry, or None past the end
+    ///
+    pub fn get(&self, ind: usize) -> Option<Program> {
+        if ind >= self.count {
+            return None;
+        }
+        return self.progs[ind];
+    }
+
+    ///
+    /// list - prints the name of every registered program
+    ///
+    pub fn list(&self) {
+        for i in 0..self.count {
+            if let Some(prog) = self.progs[i] {
+                print!(" {}", prog.name);
+            }
+        }
+        println!();
+    }
+}
+
+/// Our Global program registry
+lazy_static! {
+    pub static ref PROGS: Mutex<ProgTbl> = Mutex::new(ProgTbl {
+        progs: [None; MAX_PROGRAMS],
+        count: 0,
+    });
+}
+
+///
+/// register - adds a program to the global registry
+///
+/// params:
+///     name: what exec and spawn call it
+///     desc: one line about what it does
+///     main: entry point
+///     prio: priority it runs at (common::PRIO_*)
+///
+pub fn register(name: &'static str, desc: &'static str, main: Main, prio: u8) {
+    PROGS.lock().register(Program {
+        name: name,
+        desc: desc,
+        main: main,
+        prio: prio,
+    });
+}
+
+///
+/// find - looks up a program in the global registry
+///
+/// param:
+///     name: the program's name
+///
+/// returns:
+///     a copy of the program's entry, or None if there's no such program
+///
+pub fn find(name: &[u8]) -> Option<Program> {
+    return PROGS.lock().find(name);
+}
+
+///
+/// info - fills in what user space gets to see about a program
+///
+/// params:
+///     prog: the program
+///     info: where to put it
+///
+pub fn info(prog: &Program, info: &mut common::ProgInfo) {
+    let name = prog.name.as_bytes();
+    let desc = prog.desc.as_bytes();
+    info.name = [0; common::MAX_PROG_NAME];
+    info.desc = [0; common::MAX_PROG_DESC];
+    for i in 0..name.len() {
+        info.name[i] = name[i];
+    }
+    // Descriptions get cut short rather than refused
+    for i in 0..desc.len().min(common::MAX_PROG_DESC - 1) {
+        info.desc[i] = desc[i];
+    }
+    info.prio = prog.prio;
+}
+
+///
+/// Registers every module's programs and lists them
+///
+pub fn _programs_init() {
+    print!("PROGRAMS:");
+    users::register_programs();
+    PROGS.lock().list();
+}
@@ -9,7 +9,6 @@
 
 use core::ptr;
 use core::ffi;
-use core::mem;
 use spin::Mutex;
 use lazy_static::lazy_static;
 use crate::println;
@@ -17,23 +16,36 @@ use crate::x86arch;
 use crate::pcbs::Pcb;
 use crate::pcbs;
 use crate::stacks;
+use crate::common;
+use crate::files;
+use crate::vma;
+use crate::paging;
+use crate::space;
+use crate::gdt;
+use alloc::boxed::Box;
 
 extern "C" {
     #[no_mangle]
-    fn _kmalloc(size:u64) -> usize;
+    fn __kswitch(ksp: *mut u64);
 }
 
 /// How many processes do we have?
 pub const NUM_PROC: u8 = 8;
 
+/// What _add_proc and find_zombie return when there's no such slot
+pub const NO_SLOT: usize = 9;
+
 /// Quantum size
 const QUANTUM_STD: u8 = 5;
 
+/// Quantum for each priority, indexed by common::PRIO_*
+const QUANTUM: [u8; common::NUM_PRIOS as usize] = [QUANTUM_STD * 2, QUANTUM_STD, 2];
+
 /// Scheduler struct
 pub struct Scheduler {
-    proc_stat: &'static mut ProcStatus, // Array telling us if a process is in use
-    procs: &'static mut Procs,  // Active queue
-    q: &'static mut ProcSched,  // Scheduled process queue
+    proc_stat: Box<ProcStatus>, // Array telling us if a process is in use
+    procs: Box<Procs>,          // Active queue
+    q: Box<ProcSched>,          // Scheduled process queue
     in_use: u8,    // Number of active boys
     current: u8,   // Current process index
     sched_ptr: u8, // Pointer to where we are in q
@@ -47,6 +59,39 @@ struct ProcSched {
     data: [i8; (NUM_PROC as usize)],
 }
 
+/// Processes blocked in the kernel until something happens, first come
+/// first served
+pub struct WaitQueue {
+    data: [i8; (NUM_PROC as usize)], // indexes into the active queue
+    len: usize,
+}
+
+impl WaitQueue {
+    /// A queue with nobody on it
+    pub fn new() -> WaitQueue {
+        return WaitQueue { data: [-1; NUM_PROC as usize], len: 0 };
+    }
+
+    /// Adds a process to the back
+    fn push(&mut self, ind: i8) {
+        self.data[self.len] = ind;
+        self.len += 1;
+    }
+
+    /// Takes the process off the front, if there is one
+    fn pop(&mut self) -> Option<i8> {
+        if self.len == 0 {
+            return None;
+        }
+        let ind = self.data[0];
+        for i in 1..self.len {
+            self.data[i - 1] = self.data[i];
+        }
+        self.len -= 1;
+        return Some(ind);
+    }
+}
+
 struct Procs {
     data: [Pcb; (NUM_PROC as usize)],
 }
@@ -58,20 +103,21 @@ impl Scheduler {
     ///
     /// param:
     ///     cxt: context pointer as ulong
-    ///     stk: pointer to base of the stack as ulong
+    ///     stk: pointer to base of the kernel stack as ulong
     ///     event: not really used by anything
     ///     extst: exit status, shouldn't really be set here
     ///     pid: process id
     ///     ppid: parent process id
     ///     children: number of children (always 0 here)
+    ///     space: its address space, from space::new
     ///
     /// returns:
-    ///     index into active queue of new process
+    ///     index into active queue of new process, or NO_SLOT if it's full
     ///
     pub fn _add_proc(&mut self, cxt: u64, stk:u64, event:u32, extst:u32,
-                     pid:u16, ppid:u16, children:u16) -> usize {
-        if self.in_use == 7 {
-            return 9;
+                     pid:u16, ppid:u16, children:u16, space: paging::PageTable) -> usize {
+        if self.in_use >= NUM_PROC {
+            return NO_SLOT;
         }
         let mut next = 0 as usize;
         // First process
@@ -81,11 +127,6 @@ impl Scheduler {
             self.procs.data[0].spot = 0;
             self.proc_stat.data[0]  = 1;
         }
-        // Useless block, whoops
-        else if self.in_use == NUM_PROC {
-            //None
-            return 9;
-        }
         // Find empty process in active queue
         else {
             for i in 1..NUM_PROC {
@@ -99,12 +140,10 @@ impl Scheduler {
         }
         self.in_use += 1;
 
-        // Do our gross casts
-        unsafe {
-            self.procs.data[next].cxt        = &mut *(cxt as *mut pcbs::Context);
-
-            self.procs.data[next].stack      = &mut *(stk as *mut stacks::StkBuffer);
-        }
+        self.procs.data[next].cxt        = cxt as *mut pcbs::Context;
+        self.procs.data[next].kstack     = stk;
+        self.procs.data[next].ksp        = 0;
+        self.procs.data[next].space      = space;
 
         // Set up the rest
         self.procs.data[next].event      = event;
@@ -114,6 +153,17 @@ impl Scheduler {
         self.procs.data[next].ppid       = ppid;
         self.procs.data[next].children   = children;
         self.procs.data[next].state      = pcbs::ST_READY;
+        self.procs.data[next].trace      = 0;
+        self.procs.data[next].prio       = common::PRIO_STD;
+        self.procs.data[next].pgid       = pid;
+        self.procs.data[next].fds        = [None; files::MAX_FDS];
+        self.procs.data[next].heap       = 0;
+        self.procs.data[next].brk        = 0;
+        self.procs.data[next].stack_bottom = space::STACK_BASE;
+        self.procs.data[next].stack_limit  = space::STACK_MAX;
+        self.procs.data[next].vmas       = [None; vma::MAX_VMAS];
+        self.procs.data[next].sysmask    = !0;
+        self.procs.data[next].syspolicy  = common::SYSPOL_EPERM;
 
         return next;
         //self.procs.data[next].state = pcbs::e_states::ST_READY;
@@ -144,7 +194,12 @@ impl Scheduler {
 
         let ind  = self.q.data[self.current as usize] as usize;
         self.procs.data[ind].state = pcbs::ST_RUNNING;
-        self.procs.data[ind].ticks = QUANTUM_STD;
+        self.procs.data[ind].ticks = QUANTUM[self.procs.data[ind].prio as usize];
+        self.procs.data[ind].space.activate();
+
+        // Interrupts and syscalls from the process land on its kernel stack
+        let kstk = self.procs.data[ind].kstack;
+        gdt::set_rsp0(kstk + stacks::STACK_BYTES);
     }
 
     ///
@@ -155,10 +210,24 @@ impl Scheduler {
     ///
     pub fn get_curr_cxt(&mut self) -> u64 {
         let ind  = self.q.data[self.current as usize] as usize;
-        let curr = &mut *(self.procs.data[ind].cxt) as *mut pcbs::Context;
+        let curr = self.procs.data[ind].cxt;
         return curr as u64;
     }
 
+    ///
+    /// Takes the kernel stack pointer the current process blocked at
+    ///
+    /// returns:
+    ///     the stack pointer __kswitch saved, or 0 if the process isn't
+    ///     blocked in the kernel and resumes from its context instead
+    ///
+    pub fn take_curr_ksp(&mut self) -> u64 {
+        let ind = self.q.data[self.current as usize] as usize;
+        let ksp = self.procs.data[ind].ksp;
+        self.procs.data[ind].ksp = 0;
+        return ksp;
+    }
+
     ///
     /// Gets pointer to curr proc
     ///
@@ -172,14 +241,71 @@ impl Scheduler {
     }
 
     ///
-    /// Sets pointer to curr proc's context
+    /// Gets pointer to the process at an index in the active queue
+    ///
+    /// param:
+    ///     ind: index in active queue
+    ///
+    /// returns:
+    ///     ulong that points to the process struct
+    ///
+    pub fn get_proc(&mut self, ind:i8) -> u64 {
+        let curr = &mut (self.procs.data[ind as usize]) as *mut Pcb;
+        return curr as u64;
+    }
+
+    ///
+    /// Finds a live process by pid
+    ///
+    /// param:
+    ///     pid: process id to look for
+    ///
+    /// returns:
+    ///     ulong that points to the process struct, or 0 if there isn't one
+    ///
+    pub fn find_pid(&mut self, pid:u16) -> u64 {
+        for i in 0..NUM_PROC {
+            if self.proc_stat.data[i as usize] != 0 &&
+                self.procs.data[i as usize].pid == pid {
+                    return self.get_proc(i as i8);
+            }
+        }
+        return 0;
+    }
+
+    ///
+    /// Finds the index'th live process, counting slots in order
+    ///
+    /// param:
+    ///     index: which live process
+    ///
+    /// returns:
+    ///     ulong that points to the process struct, or 0 past the last one
+    ///
+    pub fn nth_proc(&mut self, index:u64) -> u64 {
+        let mut seen = 0;
+        for i in 0..NUM_PROC {
+            if self.proc_stat.data[i as usize] != 0 {
+                if seen == index {
+                    return self.get_proc(i as i8);
+                }
+                seen += 1;
+            }
+        }
+        return 0;
+    }
+
+    ///
+    /// Sets pointer to curr proc's context. The context was saved on the
+    /// process' kernel stack, which is kernel memory, so the pointer is
+    /// good whatever address space is loaded.
     ///
     /// param:
     ///     rsp: ulong that points to context
     ///
     pub fn set_curr_cxt(&mut self, rsp:u64) {
         let ind  = self.q.data[self.current as usize] as usize;
-        self.procs.data[ind].cxt = unsafe { &mut *(rsp as *mut pcbs::Context) };
+        self.procs.data[ind].cxt = rsp as *mut pcbs::Context;
     }
 
     ///
@@ -188,8 +314,8 @@ impl Scheduler {
     pub fn dump_curr(&mut self) {
         let curr = &self.procs.data[self.current as usize];
         println!("cxt: {:p}",curr.cxt);
-        println!("rsp: {:x}",curr.cxt.rsp);
-        println!("stk: {:p}",curr.stack);
+        println!("rsp: {:x}",curr.cxt().rsp);
+        println!("stk: {:x}",curr.kstack);
         println!("pid: {:x}",curr.pid);
         println!("ppid: {:x}",curr.ppid);
         println!("children: {:x}",curr.children);
@@ -212,50 +338,96 @@ impl Scheduler {
     }
 
     ///
-    /// Turns process into a zombie
+    /// Turns process into a zombie. Whoever waits for it cleans it up, so
+    /// its kernel stack, which we may be running on, stays put until then;
+    /// the caller wakes up CHILD_EXIT.
     ///
     /// param:
     ///     ind: index of process to bite in active queue
     ///
     pub fn bite(&mut self, ind: i8) {
-
-        // Reparent the zombie's children init
-        if self.procs.data[ind as usize].children > 0 {
-            for i in 0..NUM_PROC {
-                if i as i8 != ind {
-                    if self.procs.data[ind as usize].ppid == ind as u16 {
-                        self.procs.data[i as usize].ppid        = 0;
-                        self.procs.data[ind as usize].children -= 1;
-                        self.procs.data[0].children            += 1;
-                    }
+        let pid  = self.procs.data[ind as usize].pid;
+        let init = self.find_slot(pcbs::PID_INIT);
+
+        // Reparent the zombie's children to init, whose sys_wait will
+        // reap any that are zombies already
+        for i in 0..NUM_PROC as usize {
+            if i != ind as usize && self.proc_stat.data[i] == 1 &&
+                self.procs.data[i].ppid == pid {
+                self.procs.data[i].ppid = pcbs::PID_INIT;
+                self.procs.data[ind as usize].children -= 1;
+                if init != NO_SLOT {
+                    self.procs.data[init].children += 1;
                 }
             }
         }
+        self.procs.data[ind as usize].state = pcbs::ST_ZOMBIE;
+    }
 
-        // Find the index of zombie's parent
-        let mut parent = 0 as usize;
-        for i in 0..NUM_PROC {
-            if self.procs.data[i as usize].pid == self.procs.data[ind as usize].ppid {
-                parent = i as usize;
+    ///
+    /// Tells whether one process descends from another
+    ///
+    /// params:
+    ///     pid: the process to check
+    ///     ancestor: who it might descend from
+    ///
+    /// returns:
+    ///     true if following pid's parents gets to ancestor
+    ///
+    pub fn is_descendant(&self, pid: u16, ancestor: u16) -> bool {
+        let mut slot = self.find_slot(pid);
+        // Parents are never further up than there are processes
+        for _ in 0..NUM_PROC {
+            if slot == NO_SLOT || self.procs.data[slot].pid == pcbs::PID_INIT {
+                return false;
             }
+            let ppid = self.procs.data[slot].ppid;
+            if ppid == ancestor {
+                return true;
+            }
+            slot = self.find_slot(ppid);
         }
-        // If parent is waiting, cleanup zombie
-        if self.procs.data[parent].state == pcbs::ST_WAITING {
-            //println!("p found");
-            self.procs.data[parent].cxt.rax   = self.procs.data[ind as usize].pid as u64;
-            self.procs.data[parent].state     = pcbs::ST_READY;
-            self.procs.data[parent].children -= 1;
-            let sched_spot = self.procs.data[parent].spot;
-            self._schedule(sched_spot);
-            self.in_use -= 1;
-            self.proc_stat.data[ind as usize] = 0;
+        return false;
+    }
+
+    ///
+    /// Tells whether any live process is in a process group
+    ///
+    /// param:
+    ///     pgid: the group
+    ///
+    pub fn group_exists(&self, pgid: u16) -> bool {
+        for i in 0..NUM_PROC as usize {
+            if self.proc_stat.data[i] == 1 && self.procs.data[i].pgid == pgid &&
+                self.procs.data[i].state != pcbs::ST_ZOMBIE {
+                return true;
+            }
         }
-        // If not, issa zombie
-        else {
-            //println!("p not found");
-            self.procs.data[ind as usize].state = pcbs::ST_ZOMBIE;
+        return false;
+    }
+
+    ///
+    /// Sets the trace flags of every live process
+    ///
+    /// param:
+    ///     flags: new common::TRACE_* flags, 0 to stop tracing
+    ///
+    pub fn trace_all(&mut self, flags: u8) {
+        for i in 0..NUM_PROC as usize {
+            if self.proc_stat.data[i] == 1 {
+                self.procs.data[i].trace = flags;
+            }
         }
+    }
 
+    /// Finds the index of a live process by pid, NO_SLOT if there isn't one
+    fn find_slot(&self, pid: u16) -> usize {
+        for i in 0..NUM_PROC as usize {
+            if self.proc_stat.data[i] == 1 && self.procs.data[i].pid == pid {
+                return i;
+            }
+        }
+        return NO_SLOT;
     }
 
     ///
@@ -265,12 +437,13 @@ impl Scheduler {
     ///     ppid: parent that is waiting
     ///
     /// returns:
-    ///     9 if no zombie child, child's index in active queue if it exists
+    ///     NO_SLOT if no zombie child, child's index in active queue if it exists
     ///
     pub fn find_zombie(&mut self, ppid: u16) -> i8 {
-        let mut ret = 9 as i8;
+        let mut ret = NO_SLOT as i8;
         for i in 0..NUM_PROC {
-            if self.procs.data[i as usize].ppid == ppid &&
+            if self.proc_stat.data[i as usize] == 1 &&
+                self.procs.data[i as usize].ppid == ppid &&
                 self.procs.data[i as usize].state == pcbs::ST_ZOMBIE {
                     ret = i as i8;
                     break;
@@ -293,12 +466,15 @@ impl Scheduler {
     }
 
     ///
-    /// Cleans up a process (should free stack, though)
+    /// Cleans up a process and gives back its stacks and page tables
     ///
     /// param:
     ///     ind: index of process to clean up
     ///
     pub fn rem_pcb(&mut self, ind:i8) {
+        space::destroy(&mut self.procs.data[ind as usize].space);
+        let stk = self.procs.data[ind as usize].kstack;
+        stacks::stk_free(stk);
         self.proc_stat.data[ind as usize] = 0;
         self.procs.data[ind as usize].state = pcbs::ST_UNUSED;
         self.in_use -= 1;
@@ -308,15 +484,62 @@ impl Scheduler {
 /// Scheduler global
 lazy_static! {
     pub static ref SCHED: Mutex<Scheduler> = Mutex::new(Scheduler {
-        proc_stat: unsafe { &mut *(_kmalloc(NUM_PROC as u64) as *mut ProcStatus) },
-        procs: unsafe { &mut *(_kmalloc((NUM_PROC as u64) * (mem::size_of::<Pcb>() as u64)) as *mut Procs) },
-        q: unsafe { &mut *(_kmalloc(NUM_PROC as u64) as *mut ProcSched) },
+        proc_stat: Box::new(ProcStatus { data: [0; NUM_PROC as usize] }),
+        procs: Box::new(Procs { data: [Pcb::EMPTY; NUM_PROC as usize] }),
+        q: Box::new(ProcSched { data: [-1; NUM_PROC as usize] }),
         in_use: 0,
         current: NUM_PROC - 1,
         sched_ptr: 0,
     });
 }
 
+/// Parents blocked in sys_wait; woken whenever a process exits
+lazy_static! {
+    pub static ref CHILD_EXIT: Mutex<WaitQueue> = Mutex::new(WaitQueue::new());
+}
+
+///
+/// block - puts the current process to sleep on a wait queue and runs
+/// something else. Returns once the process has been woken up and picked
+/// to run again, with everything in the calling syscall as it was. Only
+/// for syscalls: it has to be called on the process' kernel stack.
+///
+/// params:
+///     q: the queue to wait on
+///     state: what the process is doing meanwhile, e.g. pcbs::ST_WAITING
+///
+pub fn block(q: &Mutex<WaitQueue>, state: u8) {
+    let curr = unsafe { &mut *(SCHED.lock().get_curr() as *mut Pcb) };
+    curr.state = state;
+    q.lock().push(curr.spot);
+    SCHED.lock()._dispatch();
+    unsafe { __kswitch(&mut curr.ksp) };
+}
+
+///
+/// wake_all - makes every process on a wait queue ready to run again
+///
+/// param:
+///     q: the queue
+///
+pub fn wake_all(q: &Mutex<WaitQueue>) {
+    loop {
+        let ind = match q.lock().pop() {
+            Some(ind) => ind,
+            None => break,
+        };
+        let mut sched = SCHED.lock();
+        sched.procs.data[ind as usize].state = pcbs::ST_READY;
+        sched._schedule(ind);
+    }
+}
+
+/// Wraps call to take_curr_ksp for external assembly use
+#[no_mangle]
+pub fn take_curr_ksp_wrap() -> u64 {
+    return SCHED.lock().take_curr_ksp();
+}
+
 /// Wraps call to get_curr_cxt for external assembly use
 #[no_mangle]
 pub fn get_curr_cxt_wrap() -> u64 {
@@ -0,0 +1,149 @@
+///
+/// space.rs
+///
+/// Author: Jonathan Schenk
+///
+/// Where things go in a process' address space. The kernel keeps the low
+/// part (everything the kernel's page tables map, shared by every process);
+/// the stack, heap and mmap regions sit at the same addresses in every
+/// process, above USER_BASE. They're all frames from frames.rs, which fork
+/// shares copy-on-write. The kernel's time page is mapped read-only at the
+/// bottom.
+///
+/// A stack gets STACK_MAX bytes of address space but starts out with just
+/// stacks::STACK_BYTES of it mapped; the page fault handler maps more as it
+/// grows down (see stacks::stack_grow). There's a guard page below the
+/// whole reservation.
+///
+////////////////////////////////////////////////////////////////////////////////
+
+use crate::clock;
+use crate::common;
+use crate::paging;
+use crate::stacks;
+
+/// Start of the process' part of the address space (top level entry 1)
+pub const USER_BASE: u64 = 0x80_0000_0000;
+
+/// Where processes can read the kernel's time page (see ulibs::fast_time)
+pub const TIME_PAGE: u64 = USER_BASE;
+
+/// The stack sits right below STACK_TOP; STACK_BASE is its bottom to
+/// start with
+pub const STACK_TOP: u64 = USER_BASE + 0x4000_0000;
+pub const STACK_BASE: u64 = STACK_TOP - stacks::STACK_BYTES;
+
+/// Most bytes a stack can grow to, and where that leaves its bottom
+pub const STACK_MAX: u64 = 1024 * 1024;
+pub const STACK_RESERVE: u64 = STACK_TOP - STACK_MAX;
+
+/// Running off the bottom of the reservation lands here (see fault::_pf_isr)
+pub const STACK_GUARD: u64 = STACK_RESERVE - paging::PAGE_SIZE;
+
+/// Where the heap starts
+pub const HEAP_BASE: u64 = USER_BASE + 0x8000_0000;
+
+/// Where mmap regions go; slot n of the region table is at
+/// MMAP_BASE + n * MMAP_MAX
+pub const MMAP_BASE: u64 = USER_BASE + 0xc000_0000;
+
+/// How process memory is mapped unless asked otherwise
+pub const USER_FLAGS: u64 = paging::PG_WRITE | paging::PG_USER | paging::PG_NX;
+
+///
+/// new - makes an address space for a new process, with the time page
+/// and a stack in it
+///
+/// param:
+///     parent: when forking, the parent's address space, whose stack is
+///             shared copy-on-write, however far it has grown; otherwise
+///             the stack starts out STACK_BYTES long and painted (see
+///             stack_used)
+///
+/// returns:
+///     the address space, or None if there's no memory for it
+///
+pub fn new(parent: Option<&paging::PageTable>) -> Option<paging::PageTable> {
+    let mut pt = paging::PageTable::new_user()?;
+    let time = unsafe { &clock::__time_page as *const clock::TimePageBuf } as u64;
+    let mut ret = pt.map(TIME_PAGE, time, paging::PG_USER | paging::PG_NX);
+    if ret == common::E_SUCCESS {
+        ret = pt.guard(STACK_GUARD);
+    }
+    if ret == common::E_SUCCESS {
+        ret = match parent {
+            Some(parent) => parent.share_cow(&mut pt, STACK_RESERVE, STACK_MAX),
+            None => pt.map_frames(STACK_BASE, stacks::STACK_BYTES, USER_FLAGS),
+        };
+    }
+    if ret == common::E_SUCCESS && parent.is_none() {
+        for page in (STACK_BASE..STACK_TOP).step_by(paging::PAGE_SIZE as usize) {
+            if let Some(phys) = pt.translate(page) {
+                stacks::paint(phys, paging::PAGE_SIZE);
+            }
+        }
+    }
+    if ret != common::E_SUCCESS {
+        destroy(&mut pt);
+        return None;
+    }
+    return Some(pt);
+}
+
+///
+/// destroy - frees an address space from new, stack and all. The heap and
+/// mmap regions have to be gone already.
+///
+/// param:
+///     pt: the address space
+///
+pub fn destroy(pt: &mut paging::PageTable) {
+    if pt.root() != 0 {
+        pt.unmap_frames(STACK_RESERVE, STACK_MAX);
+        pt.destroy();
+    }
+}
+
+///
+/// stack_top - gets the top page of a process' stack ready for the kernel
+/// to write the process' arguments into. If the page is shared
+/// copy-on-write the process gets its own copy first, since the kernel's
+/// writes go around the page tables.
+///
+/// param:
+///     pt: the process' address space
+///
+/// returns:
+///     the kernel's address for the page, or 0 if there's no memory for
+///     the copy
+///
+pub fn stack_top(pt: &mut paging::PageTable) -> u64 {
+    let virt = STACK_TOP - paging::PAGE_SIZE;
+    if pt.entry(virt) & paging::PG_COW != 0 && !pt.copy_on_write(virt) {
+        return 0;
+    }
+    return pt.translate(virt).unwrap_or(0);
+}
+
+///
+/// stack_used - measures a process' stack high-water mark, for tuning
+/// stacks::STACK_SIZE. Forked processes inherit their parent's mark, and
+/// exec doesn't reset it.
+///
+/// param:
+///     pt: the process' address space
+///
+/// returns:
+///     bytes from STACK_TOP down to the lowest word ever written
+///
+pub fn stack_used(pt: &paging::PageTable) -> u64 {
+    for page in (STACK_RESERVE..STACK_TOP).step_by(paging::PAGE_SIZE as usize) {
+        if let Some(phys) = pt.translate(page) {
+            let used = stacks::high_water(phys, paging::PAGE_SIZE);
+            if used != 0 {
+                return STACK_TOP - page - paging::PAGE_SIZE + used;
+            }
+        }
+    }
+    return 0;
+}
@@ -3,163 +3,413 @@
 ///
 /// Author: Jonathan Schenk
 ///
-/// This file contains code for setting up user stacks and holds the OS'
-/// system stack.
+/// This file contains code for setting up user stacks, which grow on
+/// demand (see stack_grow), and heaps. Each process also
+/// has a kernel stack of its own: the CPU saves its context at the top when
+/// it enters the kernel, and interrupts and syscalls run below that, so a
+/// syscall can block partway through (see scheduler::block).
 ///
 ////////////////////////////////////////////////////////////////////////////////
 
 use core::ptr;
 use core::ffi;
 use core::mem;
-use spin::Mutex;
-use lazy_static::lazy_static;
-use crate::println;
 use crate::x86arch;
 use crate::common;
+use crate::kalloc;
+use crate::paging;
+use crate::space;
 use crate::pcbs::Pcb;
 use crate::pcbs::Context;
 use crate::pcbs;
 
-/// We should probably have kfree in here too, oops
 extern "C" {
-    #[no_mangle]
-    fn _kmalloc(size:u64) -> usize;
     #[no_mangle]
     fn do_exit();
 }
 
 pub const STACK_SIZE: usize = 1024;
 
-/// The system's kernel stack
-pub struct SysStack {
-    sys_stack: &'static mut StkBuffer,
-    sys_rsp: u64,
-}
+/// Size of a stack in bytes
+pub const STACK_BYTES: u64 = STACK_SIZE as u64 * 8;
+
+/// How far below rsp a fault can be and still grow the stack, leaving room
+/// for the 128 byte red zone
+const STACK_SLACK: u64 = 256;
+
+/// What unused stack is filled with (see high_water)
+pub const STACK_PAINT: u64 = 0x5354_4b5f_5041_494e;
+
+/// Most bytes a process' heap can grow to
+pub const HEAP_MAX: u64 = 64 * 1024;
 
 /// Stack type
 pub struct StkBuffer {
     pub data: [u64; STACK_SIZE],
 }
 
-impl SysStack {
+/// Arguments and environment for a new process, kept in kernel memory until
+/// _stk_setup copies them onto its stack
+pub struct Args {
+    strs: [u8; common::MAX_ARGV_CHARS], // argv strings then envp strings, each NUL terminated
+    used: usize,                        // bytes of strs in use
+    argc: usize,
+    envc: usize,
+}
+
+impl Args {
+    /// An empty argv and envp
+    pub fn new() -> Args {
+        return Args {
+            strs: [0; common::MAX_ARGV_CHARS],
+            used: 0,
+            argc: 0,
+            envc: 0,
+        };
+    }
+
     ///
-    /// set_rsp - sets the system's stack pointer
+    /// push_arg - adds a string to argv. All of argv has to be pushed
+    ///            before any of envp.
     ///
     /// param:
-    ///     val: value to set rsp to
+    ///     s: the string, without a NUL
     ///
-    pub fn set_rsp(&mut self, val: u64) {
-        self.sys_rsp = val;
+    /// returns:
+    ///     E_SUCCESS, E_TOO_MANY_ARGS or E_TOO_MANY_ARG_CHARS
+    ///
+    pub fn push_arg(&mut self, s: &[u8]) -> i64 {
+        if self.envc > 0 || self.argc >= common::MAX_ARGUMENTS {
+            return common::E_TOO_MANY_ARGS;
+        }
+        let ret = self.push_str(s);
+        if ret == common::E_SUCCESS {
+            self.argc += 1;
+        }
+        return ret;
     }
 
     ///
-    /// get_rsp - gets the system's stack pointer
+    /// push_env - adds a string to envp
     ///
-    /// return:
-    ///     kernel rsp
+    /// param:
+    ///     s: the string, without a NUL
     ///
-    pub fn get_rsp(&mut self) -> u64 {
-        return self.sys_rsp;
+    /// returns:
+    ///     E_SUCCESS, E_TOO_MANY_ARGS or E_TOO_MANY_ARG_CHARS
+    ///
+    pub fn push_env(&mut self, s: &[u8]) -> i64 {
+        if self.envc >= common::MAX_ARGUMENTS {
+            return common::E_TOO_MANY_ARGS;
+        }
+        let ret = self.push_str(s);
+        if ret == common::E_SUCCESS {
+            self.envc += 1;
+        }
+        return ret;
+    }
+
+    ///
+    /// space - gets the unused part of the string table, so a string can
+    ///         be copied straight into it and then committed with commit()
+    ///
+    pub fn space(&mut self) -> &mut [u8] {
+        return &mut self.strs[self.used..];
     }
 
     ///
-    /// get_stack - gets the system's stack
+    /// commit - counts a string already copied in through space()
     ///
-    /// return:
-    ///     A 64 bit address that points to the base of the kernel stack
+    /// params:
+    ///     len: length of the string, without its NUL
+    ///     env: true for envp, false for argv
     ///
-    pub fn get_stack(&mut self) -> u64 {
-        let stk = self.sys_stack as *mut StkBuffer;
-        return stk as u64;
+    /// returns:
+    ///     E_SUCCESS, E_TOO_MANY_ARGS or E_TOO_MANY_ARG_CHARS
+    ///
+    pub fn commit(&mut self, len: usize, env: bool) -> i64 {
+        if len >= common::MAX_ARGV_CHARS - self.used {
+            return common::E_TOO_MANY_ARG_CHARS;
+        }
+        if env {
+            if self.envc >= common::MAX_ARGUMENTS {
+                return common::E_TOO_MANY_ARGS;
+            }
+            self.envc += 1;
+        }
+        else {
+            if self.envc > 0 || self.argc >= common::MAX_ARGUMENTS {
+                return common::E_TOO_MANY_ARGS;
+            }
+            self.argc += 1;
+        }
+        self.strs[self.used + len] = 0;
+        self.used += len + 1;
+        return common::E_SUCCESS;
     }
 
+    /// Copies a string and its NUL into the table
+    fn push_str(&mut self, s: &[u8]) -> i64 {
+        if s.len() >= common::MAX_ARGV_CHARS - self.used {
+            return common::E_TOO_MANY_ARG_CHARS;
+        }
+        for i in 0..s.len() {
+            self.strs[self.used + i] = s[i];
+        }
+        self.strs[self.used + s.len()] = 0;
+        self.used += s.len() + 1;
+        return common::E_SUCCESS;
+    }
+}
+
+///
+/// stk_alloc - allocates a kernel stack for a process. The page below it
+/// is made a guard page, so running off the bottom faults instead of
+/// scribbling on whatever kmalloc put next to it, and the stack is painted
+/// with STACK_PAINT so high_water can tell how much of it got used.
+///
+/// returns:
+///     An 64 bit that points to the base of the stack, or 0 if there's no
+///     memory for it
+///
+pub fn stk_alloc() -> u64 {
+    let block = kalloc::kmalloc(STACK_BYTES + paging::PAGE_SIZE);
+    if block == 0 {
+        return 0;
+    }
+    if paging::guard(block) != common::E_SUCCESS {
+        kalloc::kfree(block);
+        return 0;
+    }
+    let stk = block + paging::PAGE_SIZE;
+    paint(stk, STACK_BYTES);
+    return stk;
 }
 
-/// Our system stack global
-lazy_static! {
-    pub static ref STK: Mutex<SysStack> = Mutex::new(SysStack {
-        sys_stack: unsafe { &mut *(_kmalloc(STACK_SIZE as u64 * 8) as *mut StkBuffer) },
-        sys_rsp: 0,
-    });
+///
+/// stk_free - gives back a stack from stk_alloc, guard page and all
+///
+/// param:
+///     stk: base of the stack
+///
+pub fn stk_free(stk: u64) {
+    let block = stk - paging::PAGE_SIZE;
+    paging::map(block, block, paging::PG_WRITE | paging::PG_NX);
+    kalloc::kfree(block);
 }
 
-/// Wraps call to get_stack for external assembly code
-#[no_mangle]
-pub fn get_stack_wrap() -> u64 {
-    return STK.lock().get_stack();
+///
+/// is_guard - checks whether an address is in the guard page of a stack
+/// from stk_alloc
+///
+/// params:
+///     stk: base of the stack
+///     addr: the address
+///
+pub fn is_guard(stk: u64, addr: u64) -> bool {
+    return addr < stk && addr >= stk - paging::PAGE_SIZE;
 }
 
-/// Wraps call to get_rsp for external assembly code
-#[no_mangle]
-pub fn get_rsp_wrap() -> u64 {
-    return STK.lock().get_rsp();
+///
+/// paint - fills memory with STACK_PAINT
+///
+/// params:
+///     addr: where to start, 8 byte aligned
+///     len: bytes to fill
+///
+pub fn paint(addr: u64, len: u64) {
+    for i in 0..(len / 8) {
+        unsafe { ptr::write((addr + i * 8) as *mut u64, STACK_PAINT) };
+    }
+}
+
+///
+/// high_water - measures how far down a painted stack has ever been used
+///
+/// params:
+///     addr: bottom of the stack, or of a piece of it
+///     len: its size in bytes
+///
+/// returns:
+///     bytes from the top down to the lowest word that isn't paint
+///     anymore, 0 if the whole thing is untouched
+///
+pub fn high_water(addr: u64, len: u64) -> u64 {
+    for i in 0..(len / 8) {
+        if unsafe { ptr::read((addr + i * 8) as *const u64) } != STACK_PAINT {
+            return len - i * 8;
+        }
+    }
+    return 0;
 }
 
 ///
-/// stk_alloc - allocates a stack for caller
+/// heap_alloc - reserves the memory a process' heap grows into and maps
+/// it at space::HEAP_BASE, with the break at the bottom
+///
+/// param:
+///     pcb: the process
 ///
 /// returns:
-///     An 64 bit that points to the base of the stack
+///     false if there's no memory for it
 ///
-pub fn stk_alloc() -> u64 {
-    return unsafe { _kmalloc(STACK_SIZE as u64 * 8) as u64};
+pub fn heap_alloc(pcb: &mut Pcb) -> bool {
+    if pcb.space.map_frames(space::HEAP_BASE, HEAP_MAX,
+                            space::USER_FLAGS) != common::E_SUCCESS {
+        return false;
+    }
+    pcb.heap = space::HEAP_BASE;
+    pcb.brk  = pcb.heap;
+    return true;
+}
+
+///
+/// heap_free - takes a process' heap away and gives back its reserve
+///
+/// param:
+///     pcb: the process; nothing happens if it has no heap
+///
+pub fn heap_free(pcb: &mut Pcb) {
+    if pcb.heap != 0 {
+        pcb.space.unmap_frames(pcb.heap, HEAP_MAX);
+    }
+    pcb.heap = 0;
+    pcb.brk  = 0;
+}
+
+///
+/// heap_fault - reserves a process' heap the first time it touches it,
+/// so user code can use the heap at space::HEAP_BASE without asking the
+/// kernel where it is first
+///
+/// params:
+///     pcb: the process
+///     addr: the faulting address
+///
+/// returns:
+///     true if the access can be retried, false if addr isn't in the heap,
+///     the heap was already there, or there's no memory for it
+///
+pub fn heap_fault(pcb: &mut Pcb, addr: u64) -> bool {
+    if pcb.heap != 0 || addr < space::HEAP_BASE || addr >= space::HEAP_BASE + HEAP_MAX {
+        return false;
+    }
+    return heap_alloc(pcb);
 }
 
 ///
-/// stk_copy - copies the contents of one stack into another
+/// stack_grow - maps more of a process' stack after a fault below its
+/// bottom. Only faults near the stack pointer count: a push or call, or
+/// a function making room for its locals, touches memory at or just below
+/// rsp; anything further down is a wild pointer, not a growing stack.
 ///
 /// params:
-///     src: source stack
-///     dst: destination stack
+///     pcb: the process; its context has the user rsp
+///     addr: the faulting address
 ///
-pub fn stk_copy(src:u64, dst:u64) {
-    let src_buff = unsafe { &mut *(src as *mut StkBuffer) };
-    let dst_buff = unsafe { &mut *(dst as *mut StkBuffer) };
-    for i in 0..STACK_SIZE {
-        dst_buff.data[i] = src_buff.data[i];
+/// returns:
+///     true if the access can be retried, false if addr isn't one the
+///     stack can grow to (or there's no memory for it)
+///
+pub fn stack_grow(pcb: &mut Pcb, addr: u64) -> bool {
+    if addr >= pcb.stack_bottom || addr < space::STACK_TOP - pcb.stack_limit ||
+       addr < pcb.cxt().rsp.saturating_sub(STACK_SLACK) {
+        return false;
+    }
+    let bottom = addr & !(paging::PAGE_SIZE - 1);
+    let len    = pcb.stack_bottom - bottom;
+    if pcb.space.map_frames(bottom, len, space::USER_FLAGS) != common::E_SUCCESS {
+        return false;
+    }
+    let mut page = bottom;
+    while page < pcb.stack_bottom {
+        if let Some(phys) = pcb.space.translate(page) {
+            paint(phys, paging::PAGE_SIZE);
+        }
+        page += paging::PAGE_SIZE;
     }
+    pcb.stack_bottom = bottom;
+    return true;
 }
 
 ///
-/// _stk_setup - sets up the stack for a new process
+/// _stk_setup - sets up the stacks for a new process
+///
+/// The argument strings go at the very top of the user stack, then the envp
+/// and argv pointer arrays (each ending in a null pointer), then the return
+/// address. All of that fits in the stack's top page, since there are at
+/// most MAX_ARGV_CHARS of strings and MAX_ARGUMENTS of each array. The
+/// process starts with argc, argv and envp in rdi, rsi and rdx, as if
+/// main(argc, argv, envp) had been called.
+///
+/// The context block goes at the top of the kernel stack, where the CPU
+/// leaves it when the process enters the kernel, and starts the process
+/// in ring 3.
 ///
 /// params:
-///     s: process stack
+///     kstk: the process' kernel stack
+///     page: kernel address of the user stack's top page (space::stack_top)
 ///     entry: entry point for process
+///     args: argv and envp for the process
 ///
 /// returns:
-///     64 bit address of the base of the context block for this stack
+///     kernel address of the base of the context block
 ///
 #[no_mangle]
-pub fn _stk_setup(s: &'static mut StkBuffer, entry: u64) -> u64 {
+pub fn _stk_setup(kstk: &'static mut StkBuffer, page: u64, entry: u64, args: &Args) -> u64 {
     // Get address of _sys_exit
     let ext = (do_exit as *mut fn()) as u64;
 
-    // Put 0 at last index, and exit as return address
-    s.data[STACK_SIZE - 1] = 0;
-    s.data[STACK_SIZE - 2] = ext;
+    // Where the process will see an address in the page
+    let user = |addr: u64| addr - page + space::STACK_TOP - paging::PAGE_SIZE;
+
+    // Put 0 at the very top
+    let top = page + paging::PAGE_SIZE - 8;
+    unsafe { ptr::write(top as *mut u64, 0) };
+
+    // Copy the strings, then build envp and argv below them
+    let strs = (top - args.used as u64) & !7;
+    let envp = strs - (args.envc as u64 + 1) * 8;
+    let argv = envp - (args.argc as u64 + 1) * 8;
+    let mut str_addr = strs;
+    let mut start = 0;
+    for i in 0..args.used {
+        unsafe { ptr::write((strs + i as u64) as *mut u8, args.strs[i]) };
+    }
+    for i in 0..(args.argc + args.envc) {
+        let slot = if i < args.argc { argv + i as u64 * 8 }
+                   else { envp + (i - args.argc) as u64 * 8 };
+        unsafe { ptr::write(slot as *mut u64, user(str_addr)) };
+        while args.strs[start] != 0 {
+            start += 1;
+        }
+        start += 1;
+        str_addr = strs + start as u64;
+    }
+    unsafe {
+        ptr::write((argv + args.argc as u64 * 8) as *mut u64, 0);
+        ptr::write((envp + args.envc as u64 * 8) as *mut u64, 0);
+    }
+
+    // Exit as return address, placed so the stack is aligned the way a
+    // function expects right after it was called
+    let ptr = ((argv - 16) & !0xf) + 8;
+    unsafe { ptr::write(ptr as *mut u64, ext) };
 
     // Set up context block
-    let ptr = (&mut s.data[STACK_SIZE - 2] as *mut u64) as u64;
-    let ret = ptr - mem::size_of::<pcbs::Context>() as u64;
-    let cxt = unsafe { &mut *(ret as *mut Context) };
+    let base = (&mut *kstk as *mut StkBuffer) as u64;
+    let ret  = base + STACK_BYTES - mem::size_of::<pcbs::Context>() as u64;
+    let cxt  = unsafe { &mut *(ret as *mut Context) };
 
     // Set up registers for the process
     cxt.rflags = common::DEFAULT_EFLAGS as u64;
     cxt.rip = entry;
     cxt.rbp = 0;
-    cxt.cs = 0x8; // GDT64_CODE
-    cxt.ss = 0x10; // GDT64_DATA
-    cxt.rsp = ptr;
+    cxt.cs = x86arch::GDT64_UCODE | x86arch::RPL_USER;
+    cxt.ss = x86arch::GDT64_UDATA | x86arch::RPL_USER;
+    cxt.rsp = user(ptr);
+    cxt.rdi = args.argc as u64;
+    cxt.rsi = user(argv);
+    cxt.rdx = user(envp);
     return ret;
 }
-
-///
-/// Initializes system stack
-///
-pub fn _stk_init() {
-    println!("STK");
-    let stk = STK.lock().get_stack();
-    let rsp = stk + (STACK_SIZE as u64 * 8) - 2;
-    STK.lock().set_rsp(rsp);
-}
@@ -17,71 +17,204 @@ use crate::x86arch;
 use crate::pcbs;
 use crate::clock;
 use crate::stacks;
+use crate::common;
+use crate::uaccess;
+use crate::programs;
+use crate::files;
+use crate::vma;
+use crate::space;
+use crate::paging;
 use crate::println;
+use crate::print;
 
 /// Necessary C/x86 functions
 extern "C" {
-    #[no_mangle]
-    fn _kmalloc(size:u64) -> usize;
     #[no_mangle]
     fn __outb(port:i32, value:i32);
+    #[no_mangle]
+    fn __inb(port:i32) -> i32;
+    #[no_mangle]
+    fn __rdtsc() -> u64;
+    #[no_mangle]
+    fn __rdmsr(msr:u32) -> u64;
+    #[no_mangle]
+    fn __wrmsr(msr:u32, value:u64);
+    #[no_mangle]
+    fn __syscall_entry();
 }
 
 /// Syscall codes
-const SYS_exit: usize = 0;
-const SYS_fork: usize = 1;
-const SYS_exec: usize = 2;
-const SYS_time: usize = 3;
-const SYS_pid:  usize = 4;
-const SYS_ppid: usize = 5;
-const SYS_wait: usize = 6;
+pub const SYS_exit: usize = 0;
+pub const SYS_fork: usize = 1;
+pub const SYS_exec: usize = 2;
+pub const SYS_time: usize = 3;
+pub const SYS_pid:  usize = 4;
+pub const SYS_ppid: usize = 5;
+pub const SYS_wait: usize = 6;
+pub const SYS_trace: usize = 7;
+pub const SYS_progs: usize = 8;
+pub const SYS_spawn: usize = 9;
+pub const SYS_read: usize = 10;
+pub const SYS_write: usize = 11;
+pub const SYS_close: usize = 12;
+pub const SYS_dup2: usize = 13;
+pub const SYS_brk: usize = 14;
+pub const SYS_heap: usize = 15;
+pub const SYS_mmap: usize = 16;
+pub const SYS_munmap: usize = 17;
+pub const SYS_mprotect: usize = 18;
+pub const SYS_restrict: usize = 19;
+pub const SYS_procs: usize = 20;
 
-const NUM_SYSCALLS: usize = 7;
+/// Size of the syscall table. Codes must be below this.
+pub const MAX_SYSCALLS: usize = 64;
+
+/// Most arguments a syscall can take
+const MAX_ARGS: usize = 6;
 
 static INT_VEC_SYSCALL: i8 = 0x42;
 
-/// Syscall table
-struct SysTbl {
-    syscalls: &'static mut Buffer,
+/// Pressing this key (F12) on the console turns tracing of every process
+/// on or off (see _trace_key_isr)
+const TRACE_KEY: i32 = 0x58;
+
+/// Keyboard data port
+const KBD_DATA_PORT: i32 = 0x60;
+
+/// Whether TRACE_KEY last turned tracing on
+static mut TRACE_ALL: bool = false;
+
+/// Selector SYSRET builds its segments from: SS = this + 8, CS = this + 16
+const SYSRET_SEL_BASE: u64 = 0x10;
+
+/// What every syscall handler looks like. Handlers get the caller's saved
+/// registers and PCB, and leave their return value in cxt.rax.
+pub type SysHandler = fn(&mut pcbs::Context, &mut pcbs::Pcb);
+
+/// Describes one syscall
+#[derive(Clone, Copy)]
+pub struct SysDesc {
+    pub code: usize,        // value the caller puts in rax
+    pub name: &'static str, // name for listings and tracing
+    pub nargs: u8,          // number of arguments (rdi, rsi, ...)
+    pub handler: SysHandler,
 }
 
-/// Buffer for syscall table
-struct Buffer {
-    data: [fn(); NUM_SYSCALLS],
+/// Every syscall the kernel provides. Adding a syscall means adding a code
+/// above and an entry here; ulibs makes every call through one stub.
+pub static SYSCALLS: &[SysDesc] = &[
+    SysDesc { code: SYS_exit, name: "exit", nargs: 1, handler: _sys_exit },
+    SysDesc { code: SYS_fork, name: "fork", nargs: 0, handler: _sys_fork },
+    SysDesc { code: SYS_exec, name: "exec", nargs: 3, handler: _sys_exec },
+    SysDesc { code: SYS_time, name: "time", nargs: 0, handler: _sys_time },
+    SysDesc { code: SYS_pid,  name: "pid",  nargs: 0, handler: _sys_pid },
+    SysDesc { code: SYS_ppid, name: "ppid", nargs: 0, handler: _sys_ppid },
+    SysDesc { code: SYS_wait, name: "wait", nargs: 0, handler: _sys_wait },
+    SysDesc { code: SYS_trace, name: "trace", nargs: 2, handler: _sys_trace },
+    SysDesc { code: SYS_progs, name: "progs", nargs: 2, handler: _sys_progs },
+    SysDesc { code: SYS_spawn, name: "spawn", nargs: 3, handler: _sys_spawn },
+    SysDesc { code: SYS_read, name: "read", nargs: 3, handler: _sys_read },
+    SysDesc { code: SYS_write, name: "write", nargs: 3, handler: _sys_write },
+    SysDesc { code: SYS_close, name: "close", nargs: 1, handler: _sys_close },
+    SysDesc { code: SYS_dup2, name: "dup2", nargs: 2, handler: _sys_dup2 },
+    SysDesc { code: SYS_brk, name: "brk", nargs: 1, handler: _sys_brk },
+    SysDesc { code: SYS_heap, name: "heap", nargs: 0, handler: _sys_heap },
+    SysDesc { code: SYS_mmap, name: "mmap", nargs: 4, handler: _sys_mmap },
+    SysDesc { code: SYS_munmap, name: "munmap", nargs: 2, handler: _sys_munmap },
+    SysDesc { code: SYS_mprotect, name: "mprotect", nargs: 3, handler: _sys_mprotect },
+    SysDesc { code: SYS_restrict, name: "restrict", nargs: 2, handler: _sys_restrict },
+    SysDesc { code: SYS_procs, name: "procs", nargs: 2, handler: _sys_procs },
+];
+
+/// Most bytes read and write move through the kernel at once
+const IO_CHUNK: usize = 256;
+
+/// Syscall table
+pub struct SysTbl {
+    syscalls: [Option<SysDesc>; MAX_SYSCALLS],
 }
 
 impl SysTbl {
-    /// Initialize syscall table
-    pub fn _syscall_init(&mut self) {
-        self.syscalls.data[SYS_exit] = _sys_exit;
-        self.syscalls.data[SYS_fork] = _sys_fork;
-        self.syscalls.data[SYS_exec] = _sys_exec;
-        self.syscalls.data[SYS_time] = _sys_time;
-        self.syscalls.data[SYS_pid]  = _sys_pid;
-        self.syscalls.data[SYS_ppid] = _sys_ppid;
-        self.syscalls.data[SYS_wait] = _sys_wait;
-    }
-
-    /// Calls a system call
+    ///
+    /// register - adds a syscall to the table
+    ///
+    /// param:
+    ///     desc: the syscall to add
+    ///
+    /// Panics if the code is out of range or already taken, since that
+    /// can only be a mistake in the kernel.
+    ///
+    pub fn register(&mut self, desc: SysDesc) {
+        if desc.code >= MAX_SYSCALLS {
+            panic!("syscall {} has out of range code {}", desc.name, desc.code);
+        }
+        if let Some(old) = self.syscalls[desc.code] {
+            panic!("syscall {} reuses code {} from {}", desc.name, desc.code, old.name);
+        }
+        self.syscalls[desc.code] = Some(desc);
+    }
+
+    ///
+    /// lookup - finds the syscall for a code
+    ///
     /// param:
     ///     code: the syscall we want
-    pub fn _call(&mut self, code:usize) {
-        self.syscalls.data[code]();
+    ///
+    /// returns:
+    ///     a copy of the descriptor, or None if nothing has that code
+    ///
+    pub fn lookup(&self, code: usize) -> Option<SysDesc> {
+        if code >= MAX_SYSCALLS {
+            return None;
+        }
+        return self.syscalls[code];
+    }
+
+    ///
+    /// list - prints every registered syscall as name(nargs)
+    ///
+    pub fn list(&self) {
+        for desc in self.syscalls.iter() {
+            if let Some(sys) = desc {
+                print!(" {}({})", sys.name, sys.nargs);
+            }
+        }
+        println!();
     }
 }
 
 ///
 /// _sys_exit - terminates calling process
 ///
-/// implements: sys_exit()
+/// implements: sys_exit(status)
 ///
 /// no return >:)
 ///
-fn _sys_exit() {
-    let curr        = unsafe { &mut *(scheduler::SCHED.lock().get_curr() as *mut pcbs::Pcb) };
-    let status      = curr.cxt.rdi;
+fn _sys_exit(cxt: &mut pcbs::Context, curr: &mut pcbs::Pcb) {
+    terminate(curr, cxt.rdi);
+}
+
+///
+/// terminate - ends the current process, as if it had called sys_exit. A
+/// traced process gets its stack high-water marks printed on the way out.
+///
+/// params:
+///     curr: the current process
+///     status: its exit status
+///
+pub fn terminate(curr: &mut pcbs::Pcb, status: u64) {
     curr.exitstatus = status as u32;
+    if curr.trace & common::TRACE_ON != 0 {
+        let kstk = curr.kstack;
+        println!("[{}] stack high water: user {} of {} bytes, kernel {} of {}",
+                 curr.pid, space::stack_used(&curr.space), curr.stack_limit,
+                 stacks::high_water(kstk, stacks::STACK_BYTES), stacks::STACK_BYTES);
+    }
+    files::close_all(&mut curr.fds);
+    stacks::heap_free(curr);
+    vma::unmap_all(curr);
     scheduler::SCHED.lock().bite(curr.spot);
+    scheduler::wake_all(&scheduler::CHILD_EXIT);
     scheduler::SCHED.lock()._dispatch();
 }
 
@@ -91,98 +224,332 @@ fn _sys_exit() {
 /// implements: sys_fork() -> u16
 ///
 /// returns:
-///     parent - PID of new child or 9
+///     parent - PID of new child, or E_NO_PCBS, E_NO_STACKS or E_NO_MEM
 ///     child  - 0
 ///
-fn _sys_fork() {
+fn _sys_fork(cxt: &mut pcbs::Context, curr: &mut pcbs::Pcb) {
     let in_use = scheduler::SCHED.lock().get_in_use();
     if in_use >= scheduler::NUM_PROC  {
-        //TODO error too many proc
+        cxt.rax = common::E_NO_PCBS as u64;
         return;
     }
 
-    // Get current process info and duplicates it for child
-    let curr     = unsafe { &mut *(scheduler::SCHED.lock().get_curr() as *mut pcbs::Pcb) };
-    let curr_stk = (curr.stack as *mut stacks::StkBuffer) as u64;
+    // The child shares our memory copy-on-write, at the same addresses, so
+    // everything on the stack (saved frame pointers, pointers into it)
+    // still works. Only our context gets copied now, onto its kernel stack.
+    let curr_stk = curr.kstack;
     let stk      = stacks::stk_alloc();
+    if stk == 0 {
+        cxt.rax = common::E_NO_STACKS as u64;
+        return;
+    }
+    let space = match space::new(Some(&curr.space)) {
+        Some(space) => space,
+        None => {
+            stacks::stk_free(stk);
+            cxt.rax = common::E_NO_MEM as u64;
+            return;
+        }
+    };
     let pid      = pcbs::PID.lock().get_next_pid();
     let ppid     = curr.pid;
-    let children = 0;
 
-    stacks::stk_copy(curr_stk, stk);
+    let child_cxt  = stk + ((cxt as *mut pcbs::Context) as u64 - curr_stk);
+    unsafe { ptr::copy_nonoverlapping(cxt as *const pcbs::Context, child_cxt as *mut pcbs::Context, 1) };
+    let cxt_struct = unsafe { &mut *(child_cxt as *mut pcbs::Context) };
 
-    // Doing weird stuff to avoid underflow on an unsigned
-    let offset     = stk as i64 - curr_stk as i64;
-    let offset_u: u64;
-    if offset < 0 {
-        offset_u = (offset * -1) as u64
-    }
-    else {
-        offset_u = offset as u64;
-    }
-    let curr_cxt   = (&mut *(curr.cxt) as *mut pcbs::Context) as u64;
-    let mut cxt    = curr_cxt;
-    if offset < 0 {
-        cxt = curr_cxt - offset_u;
+    // Set up returns
+    cxt_struct.rax  = 0;
+    cxt.rax         = pid as u64;
+    curr.children  += 1;
+
+    // Schedule the child
+    let spot = scheduler::SCHED.lock()._add_proc(child_cxt, stk, 0, 0, pid, ppid, 0, space) as i8;
+    let child = unsafe { &mut *(scheduler::SCHED.lock().get_proc(spot) as *mut pcbs::Pcb) };
+    child.prio = curr.prio;
+    child.pgid = curr.pgid;
+    files::inherit(&mut child.fds, &curr.fds);
+    copy_heap(child, curr);
+    child.stack_bottom = curr.stack_bottom;
+    child.stack_limit  = curr.stack_limit;
+    vma::copy_all(child, curr);
+    child.sysmask   = curr.sysmask;
+    child.syspolicy = curr.syspolicy;
+    if curr.trace & common::TRACE_INHERIT != 0 {
+        child.trace = curr.trace;
     }
-    else {
-        cxt = curr_cxt + offset_u;
+    scheduler::SCHED.lock()._schedule(spot);
+}
+
+///
+/// _sys_exec - replace this program with a different one
+///
+/// implements: sys_exec(name, argv, envp) -> i64
+///
+/// argv and envp are null terminated arrays of pointers to NUL terminated
+/// strings; either may itself be null.
+///
+/// returns:
+///     Doesn't on success; E_NO_PROG, E_TOO_MANY_ARGS, E_TOO_MANY_ARG_CHARS,
+///     E_FAULT or E_NO_MEM otherwise, with the calling program left as it was
+///
+fn _sys_exec(cxt: &mut pcbs::Context, curr: &mut pcbs::Pcb) {
+    let prog = match find_user_prog(curr, cxt.rdi) {
+        Ok(prog) => prog,
+        Err(err) => {
+            cxt.rax = err as u64;
+            return;
+        }
+    };
+
+    let mut args = stacks::Args::new();
+    let mut ret = copy_strings(curr, &mut args, cxt.rsi, false);
+    if ret == common::E_SUCCESS {
+        ret = copy_strings(curr, &mut args, cxt.rdx, true);
     }
-    let cxt_struct = unsafe { &mut *(cxt as *mut pcbs::Context) };
-    if offset < 0 {
-        //cxt_struct.rbp = curr.cxt.rbp - offset_u;
-        cxt_struct.rsp = curr.cxt.rsp - offset_u;
+    if ret != common::E_SUCCESS {
+        cxt.rax = ret as u64;
+        return;
     }
-    else {
-        //cxt_struct.rbp = curr.cxt.rbp + offset_u;
-        cxt_struct.rsp = curr.cxt.rbp + offset_u;
+
+    let page = space::stack_top(&mut curr.space);
+    if page == 0 {
+        cxt.rax = common::E_NO_MEM as u64;
+        return;
     }
 
-    // This updates the childs base pointers, but apparently Rust doesn't
-    // use the bp
-/*
-    let mut bp      = cxt_struct.rbp as *mut u64;
-    let mut bp_data = bp as u64;
-    while bp as u64 != 0 && bp_data != 0 {
-        unsafe {
-            bp_data = ptr::read_volatile(bp);
+    // Nothing can fail from here on, so the old image can go
+    let stk   = unsafe { &mut *(curr.kstack as *mut stacks::StkBuffer) };
+    let new   = stacks::_stk_setup(stk, page, prog.main as u64, &args);
+    curr.cxt  = new as *mut pcbs::Context;
+    curr.prio = prog.prio;
+    stacks::heap_free(curr);
+    vma::unmap_all(curr);
+}
 
-            if offset < 0 {
-                bp_data -= offset_u;
-            }
-            else {
-                bp_data += offset_u;
-            }
+///
+/// _sys_spawn - start a program in a new child process
+///
+/// implements: sys_spawn(name, argv, &SpawnAttrs) -> i64
+///
+/// Unlike fork+exec this never shares the caller's memory; the child's
+/// stack is built from scratch like exec would. argv is the same as for exec and
+/// the attributes may be null. The child gets an empty envp. A process
+/// group to put it in has to have a live process in it already. A stack
+/// limit has to be whole pages, at least stacks::STACK_BYTES and at most
+/// space::STACK_MAX.
+///
+/// returns:
+///     pid of the child; E_NO_PROG, E_TOO_MANY_ARGS, E_TOO_MANY_ARG_CHARS,
+///     E_BAD_ARGS, E_BAD_FD, E_NO_PCBS, E_NO_STACKS, E_NO_MEM or E_FAULT
+///     otherwise
+///
+fn _sys_spawn(cxt: &mut pcbs::Context, curr: &mut pcbs::Pcb) {
+    let prog = match find_user_prog(curr, cxt.rdi) {
+        Ok(prog) => prog,
+        Err(err) => {
+            cxt.rax = err as u64;
+            return;
+        }
+    };
+
+    let mut args = stacks::Args::new();
+    let ret = copy_strings(curr, &mut args, cxt.rsi, false);
+    if ret != common::E_SUCCESS {
+        cxt.rax = ret as u64;
+        return;
+    }
 
-            ptr::write_volatile(bp, bp_data);
-            bp = ptr::read_volatile(bp_data as *mut u64) as *mut u64;
+    let mut attrs = common::SpawnAttrs {
+        flags: 0,
+        stack_limit: 0,
+        pgid: 0,
+        prio: 0,
+        nfds: 0,
+        fds: [[0; 2]; common::MAX_SPAWN_FDS],
+    };
+    if cxt.rdx != 0 {
+        let bytes = unsafe {
+            core::slice::from_raw_parts_mut(&mut attrs as *mut common::SpawnAttrs as *mut u8,
+                                            core::mem::size_of::<common::SpawnAttrs>())
+        };
+        let ret = uaccess::copy_from_user(curr, bytes, cxt.rdx);
+        if ret != common::E_SUCCESS {
+            cxt.rax = ret as u64;
+            return;
+        }
+    }
+    let known = common::SPAWN_PRIO | common::SPAWN_PGROUP | common::SPAWN_FDS |
+                common::SPAWN_STACK;
+    if attrs.flags & !known != 0 ||
+        (attrs.flags & common::SPAWN_PRIO != 0 && attrs.prio >= common::NUM_PRIOS) ||
+        attrs.nfds as usize > common::MAX_SPAWN_FDS {
+        cxt.rax = common::E_BAD_ARGS as u64;
+        return;
+    }
+    if attrs.flags & common::SPAWN_STACK != 0 &&
+        (attrs.stack_limit % paging::PAGE_SIZE != 0 ||
+         attrs.stack_limit < stacks::STACK_BYTES || attrs.stack_limit > space::STACK_MAX) {
+        cxt.rax = common::E_BAD_ARGS as u64;
+        return;
+    }
+    if attrs.flags & common::SPAWN_PGROUP != 0 && attrs.pgid != 0 &&
+        !scheduler::SCHED.lock().group_exists(attrs.pgid) {
+        cxt.rax = common::E_BAD_ARGS as u64;
+        return;
+    }
+    // The child starts with our files, then gets the remappings
+    let mut fds: files::FdTable = [None; files::MAX_FDS];
+    files::inherit(&mut fds, &curr.fds);
+    if attrs.flags & common::SPAWN_FDS != 0 {
+        for i in 0..attrs.nfds as usize {
+            let from = attrs.fds[i][0] as u64;
+            let to   = attrs.fds[i][1] as usize;
+            let file = files::get(curr, from);
+            if file.is_none() || to >= files::MAX_FDS {
+                files::close_all(&mut fds);
+                cxt.rax = common::E_BAD_FD as u64;
+                return;
+            }
+            files::set(&mut fds, to, file);
         }
     }
-     */
 
-    // Set up returns
-    cxt_struct.rax  = 0;
-    curr.cxt.rax    = pid as u64;
-    curr.children  += 1;
+    if scheduler::SCHED.lock().get_in_use() >= scheduler::NUM_PROC {
+        files::close_all(&mut fds);
+        cxt.rax = common::E_NO_PCBS as u64;
+        return;
+    }
 
-    // Schedule the child
-    let spot = scheduler::SCHED.lock()._add_proc(cxt, stk, 0, 0, pid, ppid, 0) as i8;
+    // Build the child's stack straight from the program and arguments
+    let stk_addr = stacks::stk_alloc();
+    if stk_addr == 0 {
+        files::close_all(&mut fds);
+        cxt.rax = common::E_NO_STACKS as u64;
+        return;
+    }
+    let mut space = match space::new(None) {
+        Some(space) => space,
+        None => {
+            stacks::stk_free(stk_addr);
+            files::close_all(&mut fds);
+            cxt.rax = common::E_NO_MEM as u64;
+            return;
+        }
+    };
+    let page     = space::stack_top(&mut space);
+    let stk      = unsafe { &mut *(stk_addr as *mut stacks::StkBuffer) };
+    let new      = stacks::_stk_setup(stk, page, prog.main as u64, &args);
+    let pid      = pcbs::PID.lock().get_next_pid();
+
+    let spot  = scheduler::SCHED.lock()._add_proc(new, stk_addr, 0, 0, pid, curr.pid, 0, space) as i8;
+    let child = unsafe { &mut *(scheduler::SCHED.lock().get_proc(spot) as *mut pcbs::Pcb) };
+    child.prio = prog.prio;
+    if attrs.flags & common::SPAWN_PRIO != 0 {
+        child.prio = attrs.prio;
+    }
+    child.pgid = curr.pgid;
+    child.fds  = fds;
+    child.sysmask   = curr.sysmask;
+    child.syspolicy = curr.syspolicy;
+    if attrs.flags & common::SPAWN_PGROUP != 0 {
+        child.pgid = if attrs.pgid == 0 { pid } else { attrs.pgid };
+    }
+    if attrs.flags & common::SPAWN_STACK != 0 {
+        child.stack_limit = attrs.stack_limit;
+    }
+    if curr.trace & common::TRACE_INHERIT != 0 {
+        child.trace = curr.trace;
+    }
+
+    cxt.rax        = pid as u64;
+    curr.children += 1;
     scheduler::SCHED.lock()._schedule(spot);
 }
 
 ///
-/// _sys_exec - replace this program with a different one
+/// copy_heap - gives a forked child the parent's heap, at the same
+/// address. The two share it copy-on-write. If there's no room in the
+/// child's page tables for it the child just starts without a heap.
 ///
-/// implements: sys_exec()
+/// params:
+///     child: the new process
+///     curr: its parent
+///
+fn copy_heap(child: &mut pcbs::Pcb, curr: &pcbs::Pcb) {
+    if curr.heap == 0 {
+        return;
+    }
+    if curr.space.share_cow(&mut child.space, curr.heap, stacks::HEAP_MAX) != common::E_SUCCESS {
+        child.space.unmap_frames(curr.heap, stacks::HEAP_MAX);
+        return;
+    }
+    child.heap = curr.heap;
+    child.brk  = curr.brk;
+}
+
+///
+/// find_user_prog - looks up a program whose name is in user memory
+///
+/// params:
+///     curr: the calling process
+///     addr: user address of the NUL terminated name
 ///
 /// returns:
-///     Doesn't, but probably should on error
+///     the program, or E_NO_PROG or E_FAULT
 ///
-fn _sys_exec() {
-    let curr = unsafe { &mut *(scheduler::SCHED.lock().get_curr() as *mut pcbs::Pcb) };
-    let entry = curr.cxt.rdi;
-    let cxt = stacks::_stk_setup(curr.stack, entry);
-    scheduler::set_curr_cxt_wrap(cxt);
+fn find_user_prog(curr: &pcbs::Pcb, addr: u64) -> Result<programs::Program, i64> {
+    let mut name = [0u8; common::MAX_PROG_NAME];
+    let len = uaccess::strncpy_from_user(curr, &mut name, addr);
+    if len < 0 {
+        return Err(len);
+    }
+    if len as usize >= common::MAX_PROG_NAME {
+        return Err(common::E_NO_PROG);
+    }
+    return match programs::find(&name[..len as usize]) {
+        Some(prog) => Ok(prog),
+        None => Err(common::E_NO_PROG),
+    };
+}
+
+///
+/// copy_strings - copies a user argv or envp array into args
+///
+/// params:
+///     curr: the calling process
+///     args: where the strings go
+///     list: user address of the pointer array, or 0 for none
+///     env: true if list is envp
+///
+/// returns:
+///     E_SUCCESS, E_FAULT, E_TOO_MANY_ARGS or E_TOO_MANY_ARG_CHARS
+///
+fn copy_strings(curr: &pcbs::Pcb, args: &mut stacks::Args, list: u64, env: bool) -> i64 {
+    if list == 0 {
+        return common::E_SUCCESS;
+    }
+    for i in 0..(common::MAX_ARGUMENTS + 1) as u64 {
+        let mut word = [0u8; 8];
+        let ret = uaccess::copy_from_user(curr, &mut word, list.wrapping_add(i * 8));
+        if ret != common::E_SUCCESS {
+            return ret;
+        }
+        let addr = u64::from_le_bytes(word);
+        if addr == 0 {
+            return common::E_SUCCESS;
+        }
+        let space = args.space();
+        let len = uaccess::strncpy_from_user(curr, space, addr);
+        if len < 0 {
+            return len;
+        }
+        let ret = args.commit(len as usize, env);
+        if ret != common::E_SUCCESS {
+            return ret;
+        }
+    }
+    return common::E_TOO_MANY_ARGS;
 }
 
 ///
@@ -193,9 +560,8 @@ fn _sys_exec() {
 /// returns:
 ///     system time
 ///
-fn _sys_time() {
-    let curr = unsafe { &mut *(scheduler::SCHED.lock().get_curr() as *mut pcbs::Pcb) };
-    curr.cxt.rax = clock::CLK.lock().get_time();
+fn _sys_time(cxt: &mut pcbs::Context, _curr: &mut pcbs::Pcb) {
+    cxt.rax = clock::CLK.lock().get_time();
 }
 
 ///
@@ -206,9 +572,8 @@ fn _sys_time() {
 /// returns:
 ///     PID of calling process
 ///
-fn _sys_pid() {
-    let curr = unsafe { &mut *(scheduler::SCHED.lock().get_curr() as *mut pcbs::Pcb) };
-    curr.cxt.rax = curr.pid as u64;
+fn _sys_pid(cxt: &mut pcbs::Context, curr: &mut pcbs::Pcb) {
+    cxt.rax = curr.pid as u64;
 }
 
 ///
@@ -219,65 +584,570 @@ fn _sys_pid() {
 /// returns:
 ///     PPID of calling process
 ///
-fn _sys_ppid() {
-    let curr = unsafe { &mut *(scheduler::SCHED.lock().get_curr() as *mut pcbs::Pcb) };
-    curr.cxt.rax = curr.ppid as u64;
+fn _sys_ppid(cxt: &mut pcbs::Context, curr: &mut pcbs::Pcb) {
+    cxt.rax = curr.ppid as u64;
 }
 
 ///
 /// _sys_wait - wait for child process to terminate
 ///
-/// implements: sys_wait() -> u16
+/// implements: sys_wait() -> i64
+///
+/// If no child has terminated yet, the caller blocks until one does.
 ///
 /// returns:
-///     PID of terminated child or 9 if there ain't one
+///     PID of terminated child or E_NO_KIDS if there ain't one
 ///
-fn _sys_wait() {
-    let curr = unsafe { &mut *(scheduler::SCHED.lock().get_curr() as *mut pcbs::Pcb) };
+fn _sys_wait(cxt: &mut pcbs::Context, curr: &mut pcbs::Pcb) {
+    loop {
+        if curr.children < 1 {
+            cxt.rax = common::E_NO_KIDS as u64;
+            return;
+        }
+
+        let zombo = scheduler::SCHED.lock().find_zombie(curr.pid);
+        if zombo != scheduler::NO_SLOT as i8 {
+            let child = scheduler::SCHED.lock().get_pid(zombo);
+            cxt.rax = child as u64;
+            curr.children -= 1;
+            scheduler::SCHED.lock().rem_pcb(zombo);
+            return;
+        }
 
-    if curr.children < 1 {
-        curr.cxt.rax = 9 as u64;
-        return
+        // Some process exiting wakes us; it might not be ours
+        scheduler::block(&scheduler::CHILD_EXIT, pcbs::ST_WAITING);
     }
+}
 
-    let zombo = scheduler::SCHED.lock().find_zombie(curr.ppid);
+///
+/// _sys_trace - turn syscall tracing on or off for a process
+///
+/// implements: sys_trace(pid, flags) -> i64
+///
+/// A pid of 0 means the caller. A process can only trace itself and its
+/// descendants, which can't have fewer sys_restrict limits than it has.
+///
+/// returns:
+///     the process' old trace flags, E_BAD_PID or E_PERM
+///
+fn _sys_trace(cxt: &mut pcbs::Context, curr: &mut pcbs::Pcb) {
+    let mut pid = cxt.rdi as u16;
+    let flags   = cxt.rsi as u8;
+    if pid == 0 {
+        pid = curr.pid;
+    }
+    if pid != curr.pid && !scheduler::SCHED.lock().is_descendant(pid, curr.pid) {
+        let found = scheduler::SCHED.lock().find_pid(pid);
+        let err = if found == 0 { common::E_BAD_PID } else { common::E_PERM };
+        cxt.rax = err as u64;
+        return;
+    }
+    cxt.rax = trace_pid(pid, flags) as u64;
+}
 
-    if zombo == 9 {
-        //println!("p wait");
-        curr.state = pcbs::ST_WAITING;
-        scheduler::SCHED.lock()._dispatch();
+///
+/// ISR for the keyboard, the kernel's debug switch for tracing: TRACE_KEY
+/// turns tracing on for every process and everything they start, and
+/// pressing it again turns it off. Other keys are ignored.
+///
+/// params: the usual for isrs
+///
+fn _trace_key_isr(_vector:i32, _code:i32) {
+    let key = unsafe { __inb(KBD_DATA_PORT) };
+    if key == TRACE_KEY {
+        let on = unsafe { !TRACE_ALL };
+        unsafe { TRACE_ALL = on };
+        let flags = if on { common::TRACE_ON | common::TRACE_INHERIT } else { 0 };
+        scheduler::SCHED.lock().trace_all(flags);
+        println!("tracing {}", if on { "on" } else { "off" });
     }
-    else {
-        //println!("awaken, father");
-        let child = scheduler::SCHED.lock().get_pid(zombo);
-        curr.cxt.rax = child as u64;
-        scheduler::SCHED.lock().rem_pcb(zombo);
+    unsafe { __outb(x86arch::PIC_MASTER_CMD_PORT, x86arch::PIC_EOI) };
+}
+
+///
+/// _sys_read - read from a file descriptor
+///
+/// implements: sys_read(fd, buf, len) -> i64
+///
+/// returns:
+///     bytes read, which is 0 if nothing is available; E_BAD_FD or E_FAULT
+///
+fn _sys_read(cxt: &mut pcbs::Context, curr: &mut pcbs::Pcb) {
+    let file = match files::get(curr, cxt.rdi) {
+        Some(file) => file,
+        None => {
+            cxt.rax = common::E_BAD_FD as u64;
+            return;
+        }
+    };
+    let buf = cxt.rsi;
+    let len = cxt.rdx;
+    if !uaccess::access_ok(curr, buf, len, true) {
+        cxt.rax = common::E_FAULT as u64;
+        return;
     }
 
+    // Go through a kernel buffer a chunk at a time
+    let mut chunk = [0u8; IO_CHUNK];
+    let mut done  = 0;
+    while done < len {
+        let want = if len - done < IO_CHUNK as u64 { (len - done) as usize } else { IO_CHUNK };
+        let got  = file.read(&mut chunk[..want]);
+        if got < 0 {
+            cxt.rax = (if done > 0 { done as i64 } else { got }) as u64;
+            return;
+        }
+        let ret = uaccess::copy_to_user(curr, buf + done, &chunk[..got as usize]);
+        if ret != common::E_SUCCESS {
+            cxt.rax = ret as u64;
+            return;
+        }
+        done += got as u64;
+        if (got as usize) < want {
+            break;
+        }
+    }
+    cxt.rax = done;
 }
 
 ///
-/// _sys_isr - Get the code for the desired syscall from rax then calls it.
-///            Second level call made from call to Systbl.
+/// _sys_write - write to a file descriptor
 ///
-fn _sys_isr(vector:i32, ecode:i32) {
+/// implements: sys_write(fd, buf, len) -> i64
+///
+/// returns:
+///     bytes written; E_BAD_FD or E_FAULT
+///
+fn _sys_write(cxt: &mut pcbs::Context, curr: &mut pcbs::Pcb) {
+    let file = match files::get(curr, cxt.rdi) {
+        Some(file) => file,
+        None => {
+            cxt.rax = common::E_BAD_FD as u64;
+            return;
+        }
+    };
+    let buf = cxt.rsi;
+    let len = cxt.rdx;
+    if !uaccess::access_ok(curr, buf, len, false) {
+        cxt.rax = common::E_FAULT as u64;
+        return;
+    }
+
+    let mut chunk = [0u8; IO_CHUNK];
+    let mut done  = 0;
+    while done < len {
+        let want = if len - done < IO_CHUNK as u64 { (len - done) as usize } else { IO_CHUNK };
+        let ret  = uaccess::copy_from_user(curr, &mut chunk[..want], buf + done);
+        if ret != common::E_SUCCESS {
+            cxt.rax = ret as u64;
+            return;
+        }
+        let put = file.write(&chunk[..want]);
+        if put < 0 {
+            cxt.rax = (if done > 0 { done as i64 } else { put }) as u64;
+            return;
+        }
+        done += put as u64;
+        if (put as usize) < want {
+            break;
+        }
+    }
+    cxt.rax = done;
+}
+
+///
+/// _sys_close - close a file descriptor
+///
+/// implements: sys_close(fd) -> i64
+///
+/// returns:
+///     E_SUCCESS or E_BAD_FD
+///
+fn _sys_close(cxt: &mut pcbs::Context, curr: &mut pcbs::Pcb) {
+    let fd = cxt.rdi;
+    if files::get(curr, fd).is_none() {
+        cxt.rax = common::E_BAD_FD as u64;
+        return;
+    }
+    files::set(&mut curr.fds, fd as usize, None);
+    cxt.rax = common::E_SUCCESS as u64;
+}
+
+///
+/// _sys_dup2 - make one file descriptor refer to the same file as another
+///
+/// implements: sys_dup2(old, new) -> i64
+///
+/// Whatever new referred to before is closed first.
+///
+/// returns:
+///     new, or E_BAD_FD
+///
+fn _sys_dup2(cxt: &mut pcbs::Context, curr: &mut pcbs::Pcb) {
+    let old = cxt.rdi;
+    let new = cxt.rsi;
+    let file = files::get(curr, old);
+    if file.is_none() || new as usize >= files::MAX_FDS {
+        cxt.rax = common::E_BAD_FD as u64;
+        return;
+    }
+    if old != new {
+        files::set(&mut curr.fds, new as usize, file);
+    }
+    cxt.rax = new;
+}
+
+///
+/// _sys_brk - move the program break
+///
+/// implements: sys_brk(addr) -> i64
+///
+/// The heap is reserved the first time this is called. Passing 0 just asks
+/// where the break is. New heap memory is zeroed.
+///
+/// returns:
+///     the new break, or E_NO_MEM if addr is outside the heap reserve
+///
+fn _sys_brk(cxt: &mut pcbs::Context, curr: &mut pcbs::Pcb) {
+    let addr = cxt.rdi;
+    if !heap_reserve(curr) {
+        cxt.rax = common::E_NO_MEM as u64;
+        return;
+    }
+    if addr == 0 {
+        cxt.rax = curr.brk;
+        return;
+    }
+    if addr < curr.heap || addr > curr.heap + stacks::HEAP_MAX {
+        cxt.rax = common::E_NO_MEM as u64;
+        return;
+    }
+    if addr > curr.brk {
+        unsafe { ptr::write_bytes(curr.brk as *mut u8, 0, (addr - curr.brk) as usize) };
+    }
+    curr.brk = addr;
+    cxt.rax  = addr;
+}
+
+///
+/// _sys_heap - find where the heap starts
+///
+/// implements: sys_heap() -> i64
+///
+/// returns:
+///     the heap's base, or E_NO_MEM if it couldn't be reserved
+///
+fn _sys_heap(cxt: &mut pcbs::Context, curr: &mut pcbs::Pcb) {
+    if !heap_reserve(curr) {
+        cxt.rax = common::E_NO_MEM as u64;
+        return;
+    }
+    cxt.rax = curr.heap;
+}
+
+/// Reserves curr's heap if it doesn't have one yet. False if out of memory.
+fn heap_reserve(curr: &mut pcbs::Pcb) -> bool {
+    if curr.heap == 0 {
+        return stacks::heap_alloc(curr);
+    }
+    return true;
+}
+
+///
+/// _sys_mmap - map an anonymous region
+///
+/// implements: sys_mmap(addr, len, prot, flags) -> i64
+///
+/// addr has to be 0 (the kernel picks where the region goes) and flags has
+/// to be MAP_ANON. The region is zeroed and len is rounded up to pages.
+///
+/// returns:
+///     start of the region, or E_BAD_ARGS or E_NO_MEM
+///
+fn _sys_mmap(cxt: &mut pcbs::Context, curr: &mut pcbs::Pcb) {
+    let addr  = cxt.rdi;
+    let len   = cxt.rsi;
+    let prot  = cxt.rdx;
+    let flags = cxt.r10;
+    if addr != 0 || flags != common::MAP_ANON {
+        cxt.rax = common::E_BAD_ARGS as u64;
+        return;
+    }
+    cxt.rax = vma::map(curr, len, prot) as u64;
+}
+
+///
+/// _sys_munmap - unmap a region
+///
+/// implements: sys_munmap(addr, len) -> i64
+///
+/// Only whole regions, exactly as sys_mmap returned them, can go.
+///
+/// returns:
+///     E_SUCCESS or E_BAD_ARGS
+///
+fn _sys_munmap(cxt: &mut pcbs::Context, curr: &mut pcbs::Pcb) {
+    cxt.rax = vma::unmap(curr, cxt.rdi, cxt.rsi) as u64;
+}
+
+///
+/// _sys_mprotect - change a region's protection
+///
+/// implements: sys_mprotect(addr, len, prot) -> i64
+///
+/// Like sys_munmap, this works on whole regions.
+///
+/// returns:
+///     E_SUCCESS or E_BAD_ARGS
+///
+fn _sys_mprotect(cxt: &mut pcbs::Context, curr: &mut pcbs::Pcb) {
+    cxt.rax = vma::protect(curr, cxt.rdi, cxt.rsi, cxt.rdx) as u64;
+}
+
+///
+/// _sys_progs - get information about one registered program
+///
+/// implements: sys_progs(index, &mut ProgInfo) -> i64
+///
+/// returns:
+///     E_SUCCESS, E_NO_DATA once index is past the last program, or E_FAULT
+///
+fn _sys_progs(cxt: &mut pcbs::Context, curr: &mut pcbs::Pcb) {
+    let prog = match programs::PROGS.lock().get(cxt.rdi as usize) {
+        Some(prog) => prog,
+        None => {
+            cxt.rax = common::E_NO_DATA as u64;
+            return;
+        }
+    };
+    let mut info = common::ProgInfo {
+        name: [0; common::MAX_PROG_NAME],
+        desc: [0; common::MAX_PROG_DESC],
+        prio: 0,
+    };
+    programs::info(&prog, &mut info);
+    let bytes = unsafe {
+        core::slice::from_raw_parts(&info as *const common::ProgInfo as *const u8,
+                                    core::mem::size_of::<common::ProgInfo>())
+    };
+    cxt.rax = uaccess::copy_to_user(curr, cxt.rsi, bytes) as u64;
+}
+
+///
+/// _sys_procs - get information about one live process, stack high-water
+/// marks included
+///
+/// implements: sys_procs(index, &mut ProcInfo) -> i64
+///
+/// returns:
+///     E_SUCCESS, E_NO_DATA once index is past the last process, or E_FAULT
+///
+fn _sys_procs(cxt: &mut pcbs::Context, curr: &mut pcbs::Pcb) {
+    let found = scheduler::SCHED.lock().nth_proc(cxt.rdi);
+    if found == 0 {
+        cxt.rax = common::E_NO_DATA as u64;
+        return;
+    }
+    let pcb = unsafe { &*(found as *const pcbs::Pcb) };
+    let info = common::ProcInfo {
+        pid: pcb.pid,
+        ppid: pcb.ppid,
+        state: pcb.state,
+        prio: pcb.prio,
+        _pad: [0; 2],
+        stack_used: space::stack_used(&pcb.space),
+        stack_limit: pcb.stack_limit,
+        kstack_used: stacks::high_water(pcb.kstack, stacks::STACK_BYTES),
+        kstack_size: stacks::STACK_BYTES,
+    };
+    let bytes = unsafe {
+        core::slice::from_raw_parts(&info as *const common::ProcInfo as *const u8,
+                                    core::mem::size_of::<common::ProcInfo>())
+    };
+    cxt.rax = uaccess::copy_to_user(curr, cxt.rsi, bytes) as u64;
+}
+
+///
+/// _sys_restrict - give up the right to make some syscalls
+///
+/// implements: sys_restrict(mask, policy) -> i64
+///
+/// Bit n of mask allows syscall n. The new set is whatever is allowed now
+/// and also in mask, so it can only shrink, and children get it too. exit
+/// always stays allowed. The policy can go from SYSPOL_EPERM to SYSPOL_KILL
+/// but not back.
+///
+/// returns:
+///     E_SUCCESS, or E_BAD_ARGS for an unknown policy
+///
+fn _sys_restrict(cxt: &mut pcbs::Context, curr: &mut pcbs::Pcb) {
+    let mask   = cxt.rdi;
+    let policy = cxt.rsi;
+    if policy > common::SYSPOL_KILL as u64 {
+        cxt.rax = common::E_BAD_ARGS as u64;
+        return;
+    }
+    curr.sysmask = (curr.sysmask & mask) | (1 << SYS_exit);
+    if policy as u8 > curr.syspolicy {
+        curr.syspolicy = policy as u8;
+    }
+    cxt.rax = common::E_SUCCESS as u64;
+}
+
+///
+/// trace_pid - sets the trace flags of a process. Kernel code can call this
+///             directly to start tracing something from the inside.
+///
+/// param:
+///     pid: process to trace
+///     flags: new common::TRACE_* flags, 0 to stop tracing
+///
+/// returns:
+///     the process' old trace flags, or E_BAD_PID
+///
+pub fn trace_pid(pid: u16, flags: u8) -> i64 {
+    let found = scheduler::SCHED.lock().find_pid(pid);
+    if found == 0 {
+        return common::E_BAD_PID;
+    }
+    let pcb  = unsafe { &mut *(found as *mut pcbs::Pcb) };
+    let old  = pcb.trace;
+    pcb.trace = flags;
+    return old as i64;
+}
+
+///
+/// sys_arg - fetches a syscall argument from the caller's registers
+///
+/// param:
+///     cxt: the caller's context
+///     n: which argument, starting at 0
+///
+fn sys_arg(cxt: &pcbs::Context, n: usize) -> u64 {
+    match n {
+        0 => cxt.rdi,
+        1 => cxt.rsi,
+        2 => cxt.rdx,
+        3 => cxt.r10, // SYSCALL eats rcx, so the stubs move it here
+        4 => cxt.r8,
+        _ => cxt.r9,
+    }
+}
+
+///
+/// trace_call - prints one line describing a traced syscall
+///
+/// params:
+///     sys: the syscall that was made
+///     pid: who made it
+///     args: its arguments, as they were on entry
+///     curr: the caller, after the call
+///     old: the caller's rip on entry
+///     cycles: how many TSC cycles the call took
+///
+fn trace_call(sys: &SysDesc, pid: u16, args: &[u64; MAX_ARGS],
+              curr: &mut pcbs::Pcb, old: u64, cycles: u64) {
+    print!("[{}] {}(", pid, sys.name);
+    for i in 0..(sys.nargs as usize) {
+        if i > 0 {
+            print!(", ");
+        }
+        print!("{:#x}", args[i]);
+    }
+    print!(")");
+
+    // Some calls don't come back the normal way, so don't show a stale rax
+    if sys.code == SYS_exit {
+        print!(" = ?");
+    }
+    else if curr.cxt().rip != old {
+        print!(" = 0 (new image)");
+    }
+    else {
+        print!(" = {}", curr.cxt().rax as i64);
+    }
+    println!(" <{} cycles>", cycles);
+}
+
+///
+/// _sys_dispatch - Get the code for the desired syscall from rax, look it up
+///                 in the syscall table and call its handler.
+///
+/// An unknown code terminates the caller with EXIT_BAD_CODE. A code the
+/// caller's sys_restrict mask doesn't allow fails with E_PERM or terminates
+/// it with EXIT_KILLED, depending on its policy.
+///
+fn _sys_dispatch() {
     let curr = unsafe { &mut *(scheduler::SCHED.lock().get_curr() as *mut pcbs::Pcb) };
-    let mut code = curr.cxt.rax as usize;
+    let cxt  = curr.cxt();
+    let code = cxt.rax as usize;
+
+    // Copy the descriptor out so the table isn't locked during the call
+    let desc = SYSC.lock().lookup(code);
 
-    if(code >= NUM_SYSCALLS) {
-        code = SYS_exit;
-        // TODO Set exit code to bad
+    if desc.is_some() && curr.sysmask & (1 << code) == 0 {
+        if curr.trace & common::TRACE_ON != 0 {
+            println!("[{}] syscall {} not allowed", curr.pid, code);
+        }
+        if curr.syspolicy == common::SYSPOL_KILL {
+            cxt.rdi = common::EXIT_KILLED;
+            _sys_exit(cxt, curr);
+        }
+        else {
+            cxt.rax = common::E_PERM as u64;
+        }
+        return;
     }
 
-    SYSC.lock()._call(code);
+    match desc {
+        Some(sys) => {
+            if curr.trace & common::TRACE_ON == 0 {
+                (sys.handler)(cxt, curr);
+            }
+            else {
+                let mut args = [0 as u64; MAX_ARGS];
+                for i in 0..MAX_ARGS {
+                    args[i] = sys_arg(cxt, i);
+                }
+                let pid   = curr.pid;
+                let old   = cxt.rip;
+                let start = unsafe { __rdtsc() };
+                (sys.handler)(cxt, curr);
+                let cycles = unsafe { __rdtsc() } - start;
+                trace_call(&sys, pid, &args, curr, old, cycles);
+            }
+        }
+        None => {
+            if curr.trace & common::TRACE_ON != 0 {
+                println!("[{}] bad syscall {:#x}, terminating", curr.pid, code);
+            }
+            cxt.rdi = common::EXIT_BAD_CODE;
+            _sys_exit(cxt, curr);
+        }
+    }
+}
 
+///
+/// _sys_isr - ISR for the int 0x42 syscall path. Kept so code that doesn't
+///            use SYSCALL still works.
+///
+fn _sys_isr(vector:i32, ecode:i32) {
+    _sys_dispatch();
     unsafe { __outb(x86arch::PIC_MASTER_CMD_PORT, x86arch::PIC_EOI) };
 }
 
+///
+/// _sys_fast - called by __syscall_entry once the caller's context is saved
+///
+#[no_mangle]
+pub extern "C" fn _sys_fast() {
+    _sys_dispatch();
+}
+
 /// Our Global Syscall object
 lazy_static! {
-    static ref SYSC: Mutex<SysTbl> = Mutex::new(SysTbl {
-        syscalls: unsafe { &mut *(_kmalloc(NUM_SYSCALLS as u64 * 8) as *mut Buffer) },
+    pub static ref SYSC: Mutex<SysTbl> = Mutex::new(SysTbl {
+        syscalls: [None; MAX_SYSCALLS],
     });
 }
 
@@ -285,7 +1155,23 @@ lazy_static! {
 /// Initialize syscall table
 ///
 pub fn _syscall_init() {
-    println!("SYSCALL");
-    SYSC.lock()._syscall_init();
+    print!("SYSCALL:");
+    for desc in SYSCALLS.iter() {
+        SYSC.lock().register(*desc);
+    }
+    SYSC.lock().list();
     interrupt::INT.lock().__install_isr(INT_VEC_SYSCALL as usize, _sys_isr);
+    interrupt::INT.lock().set_idt_user(INT_VEC_SYSCALL as usize);
+    interrupt::INT.lock().__install_isr(x86arch::INT_VEC_KEYBOARD, _trace_key_isr);
+
+    // Turn on SYSCALL/SYSRET and point it at __syscall_entry. SFMASK clears
+    // IF (plus TF and DF) on entry, just like the int 0x42 interrupt gate.
+    let mask = x86arch::EFLAGS_IF | x86arch::EFLAGS_TF | x86arch::EFLAGS_DF;
+    unsafe {
+        let efer = __rdmsr(x86arch::MSR_EFER);
+        __wrmsr(x86arch::MSR_EFER, efer | x86arch::EFER_SCE);
+        __wrmsr(x86arch::MSR_STAR, (SYSRET_SEL_BASE << 48) | (x86arch::GDT64_CODE << 32));
+        __wrmsr(x86arch::MSR_LSTAR, (__syscall_entry as *mut fn()) as u64);
+        __wrmsr(x86arch::MSR_SFMASK, mask as u64);
+    }
 }
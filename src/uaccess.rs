@@ -0,0 +1,201 @@
+///
+/// uaccess.rs
+///
+/// Author: Jonathan Schenk
+///
+/// Copying to and from user memory. Syscall arguments come straight out of
+/// the caller's registers, so the kernel only touches an address after
+/// checking it belongs to the calling process, and a fault during the copy
+/// comes back as E_FAULT instead of taking the kernel down.
+///
+////////////////////////////////////////////////////////////////////////////////
+
+use crate::common;
+use crate::pcbs;
+use crate::space;
+use crate::vma;
+
+/// The copy routines in uaccess.S and what the linker tells us
+extern "C" {
+    #[no_mangle]
+    fn __copy_user(dst:u64, src:u64, len:u64) -> u64;
+    #[no_mangle]
+    fn __strncpy_user(dst:u64, src:u64, max:u64) -> i64;
+    #[no_mangle]
+    static __image_start: u8;
+    #[no_mangle]
+    static __rodata_end: u8;
+    #[no_mangle]
+    static __ex_table_start: ExEntry;
+    #[no_mangle]
+    static __ex_table_end: ExEntry;
+}
+
+/// One exception table entry: if insn faults, carry on at fixup
+#[repr(C)]
+struct ExEntry {
+    insn: u64,
+    fixup: u64,
+}
+
+///
+/// room_at - how many bytes of a process' memory start at an address
+///
+/// params:
+///     pcb: the process
+///     addr: user address
+///     write: true if we want to write there
+///
+/// returns:
+///     bytes from addr to the end of the region holding it, 0 if addr
+///     isn't the process' to use
+///
+fn room_at(pcb: &pcbs::Pcb, addr: u64, write: bool) -> u64 {
+    // Its own stack, as far as it's allowed to grow; a page fault maps
+    // whatever isn't there yet
+    if addr >= space::STACK_TOP - pcb.stack_limit && addr < space::STACK_TOP {
+        return space::STACK_TOP - addr;
+    }
+
+    // Its heap
+    if pcb.heap != 0 && addr >= pcb.heap && addr < pcb.brk {
+        return pcb.brk - addr;
+    }
+
+    // Anything it mapped, if the protection allows
+    if let Some(v) = vma::find(&pcb.vmas, addr) {
+        let want = if write { common::PROT_WRITE } else { common::PROT_READ };
+        if v.prot & want != 0 {
+            return v.start + v.len - addr;
+        }
+        return 0;
+    }
+
+    // User programs and their constants are linked into the kernel image,
+    // so its text and read-only data are fair game for reading
+    if !write {
+        let img     = unsafe { &__image_start as *const u8 } as u64;
+        let img_end = unsafe { &__rodata_end as *const u8 } as u64;
+        if addr >= img && addr < img_end {
+            return img_end - addr;
+        }
+    }
+
+    return 0;
+}
+
+///
+/// access_ok - checks that a range of user memory belongs to a process
+///
+/// params:
+///     pcb: the process
+///     addr: start of the range
+///     len: length of the range in bytes
+///     write: true if we want to write to it
+///
+pub fn access_ok(pcb: &pcbs::Pcb, addr: u64, len: u64, write: bool) -> bool {
+    if len == 0 {
+        return true;
+    }
+    return room_at(pcb, addr, write) >= len;
+}
+
+///
+/// copy_from_user - copies a buffer out of a process
+///
+/// params:
+///     pcb: the process that owns src
+///     dst: where to put it
+///     src: user address to copy from
+///
+/// returns:
+///     E_SUCCESS, or E_FAULT if src isn't readable by the process
+///
+pub fn copy_from_user(pcb: &pcbs::Pcb, dst: &mut [u8], src: u64) -> i64 {
+    let len = dst.len() as u64;
+    if !access_ok(pcb, src, len, false) {
+        return common::E_FAULT;
+    }
+    let left = unsafe { __copy_user(dst.as_mut_ptr() as u64, src, len) };
+    if left != 0 {
+        return common::E_FAULT;
+    }
+    return common::E_SUCCESS;
+}
+
+///
+/// copy_to_user - copies a buffer into a process
+///
+/// params:
+///     pcb: the process that owns dst
+///     dst: user address to copy to
+///     src: what to copy
+///
+/// returns:
+///     E_SUCCESS, or E_FAULT if dst isn't writable by the process
+///
+pub fn copy_to_user(pcb: &pcbs::Pcb, dst: u64, src: &[u8]) -> i64 {
+    let len = src.len() as u64;
+    if !access_ok(pcb, dst, len, true) {
+        return common::E_FAULT;
+    }
+    let left = unsafe { __copy_user(dst, src.as_ptr() as u64, len) };
+    if left != 0 {
+        return common::E_FAULT;
+    }
+    return common::E_SUCCESS;
+}
+
+///
+/// strncpy_from_user - copies a NUL terminated string out of a process
+///
+/// params:
+///     pcb: the process that owns src
+///     dst: where to put it; the copy is NUL terminated if it fits
+///     src: user address of the string
+///
+/// returns:
+///     length of the string without the NUL, dst.len() if it didn't fit,
+///     or E_FAULT if the string isn't all in the process' memory
+///
+pub fn strncpy_from_user(pcb: &pcbs::Pcb, dst: &mut [u8], src: u64) -> i64 {
+    let room = room_at(pcb, src, false);
+    let mut max = dst.len() as u64;
+    if room < max {
+        max = room;
+    }
+
+    let len = unsafe { __strncpy_user(dst.as_mut_ptr() as u64, src, max) };
+    if len < 0 {
+        return common::E_FAULT;
+    }
+    // Ran off the end of the process' memory before finding the NUL
+    if len as u64 == max && max < dst.len() as u64 {
+        return common::E_FAULT;
+    }
+    return len;
+}
+
+///
+/// fixup_exception - if the kernel faulted in one of the copy routines,
+///                   arrange for it to resume at the matching fixup
+///
+/// param:
+///     cxt: the faulting kernel context
+///
+/// returns:
+///     true if cxt was fixed up and it is safe to return to it
+///
+pub fn fixup_exception(cxt: &mut pcbs::Context) -> bool {
+    let mut ent = unsafe { &__ex_table_start as *const ExEntry };
+    let end     = unsafe { &__ex_table_end as *const ExEntry };
+    while ent < end {
+        let e = unsafe { &*ent };
+        if e.insn == cxt.rip {
+            cxt.rip = e.fixup;
+            return true;
+        }
+        ent = unsafe { ent.add(1) };
+    }
+    return false;
+}
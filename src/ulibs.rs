@@ -9,34 +9,42 @@
 
 use crate::println;
 use crate::print;
+use crate::common::TimePage;
+use crate::common::ProgInfo;
+use crate::common::ProcInfo;
+use crate::common::SpawnAttrs;
+use crate::common;
+use crate::space;
+use crate::syscalls;
 use core::fmt;
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr;
+use core::sync::atomic::{compiler_fence, Ordering};
 
-/// All the syscall stubs we need
-/// We cheat by tricking Rust into thinking some of these actually return stuff
+/// The only syscall stubs there are. Every wrapper below goes through
+/// syscall6 with a code from syscalls.rs, so adding a syscall doesn't mean
+/// adding a stub too.
 extern "C" {
     #[no_mangle]
-    fn exit();
+    fn syscall6(code:u64, a1:u64, a2:u64, a3:u64, a4:u64, a5:u64, a6:u64) -> i64;
     #[no_mangle]
-    fn fork() -> u16;
+    fn intcall6(code:u64, a1:u64, a2:u64, a3:u64, a4:u64, a5:u64, a6:u64) -> i64;
     #[no_mangle]
-    fn exec(entry:u64) -> u16;
-    #[no_mangle]
-    fn time() -> u64;
-    #[no_mangle]
-    fn pid() -> u16;
-    #[no_mangle]
-    fn ppid() -> u16;
-    #[no_mangle]
-    fn wait() -> u16;
+    fn rdtsc() -> u64;
+}
+
+/// Makes a syscall that takes up to four arguments
+fn syscall(code:usize, a1:u64, a2:u64, a3:u64, a4:u64) -> i64 {
+    return unsafe { syscall6(code as u64, a1, a2, a3, a4, 0, 0) };
 }
 
 ///
 /// sys_exit - terminate the calling process
 ///
-/// Should take an exit status, but does not.
+/// usage: sys_exit(common::EXIT_SUCCESS)
 ///
-pub fn sys_exit() {
-    unsafe { exit() };
+pub fn sys_exit(status:u64) {
+    syscall(syscalls::SYS_exit, status, 0, 0, 0);
 }
 
 ///
@@ -45,23 +53,127 @@ pub fn sys_exit() {
 /// usage: let pid = sys_fork();
 ///
 /// Returns:
-///     parent - pid of spawned process
+///     parent - pid of spawned process, or an error code
 ///     child - 0
 ///
-pub fn sys_fork() -> u16 {
-    return unsafe { fork() };
+pub fn sys_fork() -> i64 {
+    return syscall(syscalls::SYS_fork, 0, 0, 0, 0);
 }
 
 ///
 /// sys_exec - replace this program with a different one
 ///
-/// usage: sys_exit(entry)
+/// usage: sys_exec("user_a", &["user_a", "hi"], &[])
+///
+/// The new program gets argv and envp as arguments to its main. argv[0]
+/// should be the program's name, same as everywhere else.
 ///
 /// Returns:
-///     Supposed to return on failure but doesn't? Just hope it does not fail.
+///     Nothing on success. E_NO_PROG if there is no such program,
+///     E_TOO_MANY_ARGS or E_TOO_MANY_ARG_CHARS if argv and envp don't fit.
+///
+pub fn sys_exec(name:&str, argv:&[&str], envp:&[&str]) -> i64 {
+    // The kernel wants C strings, so build them (and the pointer arrays)
+    // in a buffer on our stack
+    let mut strs = [0u8; common::MAX_ARGV_CHARS + common::MAX_PROG_NAME];
+    let mut argp = [ptr::null::<u8>(); common::MAX_ARGUMENTS + 1];
+    let mut envq = [ptr::null::<u8>(); common::MAX_ARGUMENTS + 1];
+    if argv.len() > common::MAX_ARGUMENTS || envp.len() > common::MAX_ARGUMENTS {
+        return common::E_TOO_MANY_ARGS;
+    }
+
+    let mut used = 0;
+    let prog = match c_str(&mut strs, &mut used, name) {
+        Some(p) => p,
+        None => return common::E_NO_PROG,
+    };
+    if !c_list(&mut strs, &mut used, &mut argp, argv) ||
+        !c_list(&mut strs, &mut used, &mut envq, envp) {
+        return common::E_TOO_MANY_ARG_CHARS;
+    }
+    return syscall(syscalls::SYS_exec, prog as u64, argp.as_ptr() as u64,
+                   envq.as_ptr() as u64, 0);
+}
+
+///
+/// sys_spawn - start a program in a new child process
+///
+/// usage: let pid = sys_spawn("user_a", &["user_a", "hi"], Some(&attrs))
 ///
-pub fn sys_exec(entry:u64) -> u16{
-    return unsafe { exec(entry) };
+/// Doesn't copy our stack like fork does. The child gets our file
+/// descriptors; attrs can remap them and change the child's priority,
+/// process group and how far its stack may grow. None takes the defaults.
+///
+/// Returns:
+///     pid of the child, or E_NO_PROG, E_TOO_MANY_ARGS,
+///     E_TOO_MANY_ARG_CHARS, E_BAD_ARGS, E_BAD_FD or E_NO_PCBS
+///
+pub fn sys_spawn(name:&str, argv:&[&str], attrs:Option<&SpawnAttrs>) -> i64 {
+    let mut strs = [0u8; common::MAX_ARGV_CHARS + common::MAX_PROG_NAME];
+    let mut argp = [ptr::null::<u8>(); common::MAX_ARGUMENTS + 1];
+    if argv.len() > common::MAX_ARGUMENTS {
+        return common::E_TOO_MANY_ARGS;
+    }
+
+    let mut used = 0;
+    let prog = match c_str(&mut strs, &mut used, name) {
+        Some(p) => p,
+        None => return common::E_NO_PROG,
+    };
+    if !c_list(&mut strs, &mut used, &mut argp, argv) {
+        return common::E_TOO_MANY_ARG_CHARS;
+    }
+    let attrp = match attrs {
+        Some(a) => a as *const SpawnAttrs,
+        None => ptr::null(),
+    };
+    return syscall(syscalls::SYS_spawn, prog as u64, argp.as_ptr() as u64,
+                   attrp as u64, 0);
+}
+
+/// Copies every string in src into buf, pointing list at them
+fn c_list(buf:&mut [u8], used:&mut usize, list:&mut [*const u8], src:&[&str]) -> bool {
+    for i in 0..src.len() {
+        list[i] = match c_str(buf, used, src[i]) {
+            Some(p) => p,
+            None => return false,
+        };
+    }
+    return true;
+}
+
+/// Copies s and a NUL into buf at *used, returning where it went
+fn c_str(buf:&mut [u8], used:&mut usize, s:&str) -> Option<*const u8> {
+    let bytes = s.as_bytes();
+    if bytes.len() >= buf.len() - *used {
+        return None;
+    }
+    let start = *used;
+    buf[start..start + bytes.len()].copy_from_slice(bytes);
+    buf[start + bytes.len()] = 0;
+    *used += bytes.len() + 1;
+    return Some(&buf[start] as *const u8);
+}
+
+///
+/// arg - get one of the strings main was passed in argv or envp
+///
+/// usage: let name = arg(argv, 0)
+///
+/// Returns:
+///     the string, or "" if it isn't valid UTF-8
+///
+pub fn arg(list:*const *const u8, i:u64) -> &'static str {
+    let s = unsafe { *list.add(i as usize) };
+    let mut len = 0;
+    while unsafe { *s.add(len) } != 0 {
+        len += 1;
+    }
+    let bytes = unsafe { core::slice::from_raw_parts(s, len) };
+    return match core::str::from_utf8(bytes) {
+        Ok(s) => s,
+        Err(_) => "",
+    };
 }
 
 ///
@@ -74,7 +186,46 @@ pub fn sys_exec(entry:u64) -> u16{
 ///
 #[no_mangle]
 pub fn sys_time() -> u64 {
-    return unsafe { time() };
+    return syscall(syscalls::SYS_time, 0, 0, 0, 0) as u64;
+}
+
+///
+/// fast_time - get current system time without a syscall
+///
+/// usage: let t = fast_time()
+///
+/// Reads the time page the clock ISR keeps up to date. If the clock ticked
+/// while we were reading, the sequence number won't match and we go again.
+///
+/// Returns:
+///     current system time, same as sys_time()
+///
+pub fn fast_time() -> u64 {
+    let page = unsafe { &*(space::TIME_PAGE as *const TimePage) };
+    loop {
+        let seq = unsafe { ptr::read_volatile(&page.seq) };
+        if seq & 1 == 0 {
+            compiler_fence(Ordering::SeqCst);
+            let t = unsafe { ptr::read_volatile(&page.ticks) };
+            compiler_fence(Ordering::SeqCst);
+            if unsafe { ptr::read_volatile(&page.seq) } == seq {
+                return t;
+            }
+        }
+    }
+}
+
+///
+/// time_freq - how many time units make a second
+///
+/// usage: let secs = fast_time() / time_freq()
+///
+/// Returns:
+///     clock ticks per second
+///
+pub fn time_freq() -> u64 {
+    let page = unsafe { &*(space::TIME_PAGE as *const TimePage) };
+    return unsafe { ptr::read_volatile(&page.freq) };
 }
 
 ///
@@ -86,7 +237,47 @@ pub fn sys_time() -> u64 {
 ///     current proc's pid
 ///
 pub fn sys_pid() -> u16 {
-    return unsafe { pid() };
+    return syscall(syscalls::SYS_pid, 0, 0, 0, 0) as u16;
+}
+
+///
+/// sys_pid_int - sys_pid through the old int 0x42 path
+///
+/// usage: let pid = sys_pid_int()
+///
+/// Only here so the two syscall paths can be compared.
+///
+/// Returns:
+///     current proc's pid
+///
+pub fn sys_pid_int() -> u16 {
+    return unsafe { intcall6(syscalls::SYS_pid as u64, 0, 0, 0, 0, 0, 0) } as u16;
+}
+
+///
+/// raw_syscall - make any syscall, with whatever arguments
+///
+/// usage: raw_syscall(SYS_pid as u64, &[0; 6])
+///
+/// Only for testing the kernel; normal code should use the sys_ wrappers.
+///
+/// Returns:
+///     whatever the kernel left in rax
+///
+pub fn raw_syscall(code:u64, args:&[u64; 6]) -> i64 {
+    return unsafe { syscall6(code, args[0], args[1], args[2], args[3], args[4], args[5]) };
+}
+
+///
+/// read_tsc - read the CPU's time stamp counter
+///
+/// usage: let start = read_tsc()
+///
+/// Returns:
+///     the time stamp counter, in CPU ticks
+///
+pub fn read_tsc() -> u64 {
+    return unsafe { rdtsc() };
 }
 
 ///
@@ -98,7 +289,7 @@ pub fn sys_pid() -> u16 {
 ///     current proc's ppid
 ///
 pub fn sys_ppid() -> u16 {
-    return unsafe { ppid() };
+    return syscall(syscalls::SYS_ppid, 0, 0, 0, 0) as u16;
 }
 
 ///
@@ -111,24 +302,408 @@ pub fn sys_ppid() -> u16 {
 /// returns its information; otherwise, blocks until a child terminates
 ///
 /// Returns:
-///     Only the child's pid. Not status :(
+///     Only the child's pid, or E_NO_KIDS if there are no children. Not
+///     status :(
+///
+pub fn sys_wait() -> i64 {
+    return syscall(syscalls::SYS_wait, 0, 0, 0, 0);
+}
+
+///
+/// sys_trace - turn syscall tracing on or off
+///
+/// usage: sys_trace(0, common::TRACE_ON | common::TRACE_INHERIT)
+///
+/// A pid of 0 traces the caller. Traced syscalls are logged to the console
+/// with their arguments, return value and how many TSC cycles they took.
+///
+/// Returns:
+///     the old trace flags, E_BAD_PID if there is no such process, or
+///     E_PERM if it isn't this process or one of its descendants
+///
+pub fn sys_trace(pid:u16, flags:u8) -> i64 {
+    return syscall(syscalls::SYS_trace, pid as u64, flags as u64, 0, 0);
+}
+
+///
+/// sys_read - read from a file descriptor
+///
+/// usage: let n = sys_read(0, &mut buf)
+///
+/// Returns:
+///     bytes read, 0 if there was nothing to read; E_BAD_FD if fd isn't open
+///
+pub fn sys_read(fd:u64, buf:&mut [u8]) -> i64 {
+    return syscall(syscalls::SYS_read, fd, buf.as_mut_ptr() as u64, buf.len() as u64, 0);
+}
+
+///
+/// sys_write - write to a file descriptor
+///
+/// usage: sys_write(1, b"hi\n")
+///
+/// Returns:
+///     bytes written; E_BAD_FD if fd isn't open
 ///
-pub fn sys_wait() -> u16 {
-    return unsafe { wait() };
+pub fn sys_write(fd:u64, buf:&[u8]) -> i64 {
+    return syscall(syscalls::SYS_write, fd, buf.as_ptr() as u64, buf.len() as u64, 0);
 }
 
 ///
-/// sys_spawn - an easier to use amalgamation of fork/exec.
+/// sys_close - close a file descriptor
+///
+/// usage: sys_close(3)
 ///
-/// usage: spawn(entry);
+/// Returns:
+///     E_SUCCESS, or E_BAD_FD if fd isn't open
 ///
-/// Returns: Nothin
+pub fn sys_close(fd:u64) -> i64 {
+    return syscall(syscalls::SYS_close, fd, 0, 0, 0);
+}
+
+///
+/// sys_dup2 - make new refer to the same file as old
+///
+/// usage: sys_dup2(3, 1) // stdout goes to the serial port
+///
+/// Returns:
+///     new, or E_BAD_FD
+///
+pub fn sys_dup2(old:u64, new:u64) -> i64 {
+    return syscall(syscalls::SYS_dup2, old, new, 0, 0);
+}
+
+///
+/// sys_brk - set the end of this process' heap
+///
+/// usage: let end = sys_brk(0)
+///
+/// Returns:
+///     the new break (the current one for 0), or E_NO_MEM
+///
+pub fn sys_brk(addr:u64) -> i64 {
+    return syscall(syscalls::SYS_brk, addr, 0, 0, 0);
+}
+
+///
+/// sys_heap - find where this process' heap starts
+///
+/// usage: let base = sys_heap()
+///
+/// Returns:
+///     the heap's base, or E_NO_MEM
+///
+pub fn sys_heap() -> i64 {
+    return syscall(syscalls::SYS_heap, 0, 0, 0, 0);
+}
+
+///
+/// sys_mmap - map a zeroed, page aligned region of memory
+///
+/// usage: let p = sys_mmap(0, 8192, PROT_READ | PROT_WRITE, MAP_ANON)
+///
+/// addr must be 0 and flags must be MAP_ANON for now.
+///
+/// Returns:
+///     start of the region, or E_BAD_ARGS or E_NO_MEM
+///
+pub fn sys_mmap(addr:u64, len:u64, prot:u64, flags:u64) -> i64 {
+    return syscall(syscalls::SYS_mmap, addr, len, prot, flags);
+}
+
+///
+/// sys_munmap - unmap a region from sys_mmap
+///
+/// usage: sys_munmap(p, 8192)
+///
+/// Returns:
+///     E_SUCCESS, or E_BAD_ARGS if that isn't exactly a mapped region
+///
+pub fn sys_munmap(addr:u64, len:u64) -> i64 {
+    return syscall(syscalls::SYS_munmap, addr, len, 0, 0);
+}
+
+///
+/// sys_mprotect - change the protection of a region from sys_mmap
+///
+/// usage: sys_mprotect(p, 8192, PROT_READ)
+///
+/// Returns:
+///     E_SUCCESS, or E_BAD_ARGS if that isn't exactly a mapped region
+///
+pub fn sys_mprotect(addr:u64, len:u64, prot:u64) -> i64 {
+    return syscall(syscalls::SYS_mprotect, addr, len, prot, 0);
+}
+
+///
+/// sys_restrict - give up the right to make some syscalls, for good
+///
+/// usage: sys_restrict(1 << SYS_write, common::SYSPOL_EPERM)
+///
+/// Bit n of mask keeps syscall n. Children inherit the restriction and
+/// exit is always allowed. Under SYSPOL_EPERM a denied call fails with
+/// E_PERM; under SYSPOL_KILL it terminates the process.
+///
+/// Returns:
+///     E_SUCCESS, or E_BAD_ARGS for an unknown policy
+///
+pub fn sys_restrict(mask:u64, policy:u8) -> i64 {
+    return syscall(syscalls::SYS_restrict, mask, policy as u64, 0, 0);
+}
+
+///
+/// sbrk - grow (or shrink) the heap
+///
+/// usage: let p = sbrk(4096)
+///
+/// Returns:
+///     the old break, which is the start of the new memory; or E_NO_MEM
+///
+pub fn sbrk(incr:i64) -> i64 {
+    let old = sys_brk(0);
+    if old < 0 {
+        return old;
+    }
+    let ret = sys_brk((old + incr) as u64);
+    if ret < 0 {
+        return ret;
+    }
+    return old;
+}
+
+/// How much the heap grows by at a time
+const HEAP_STEP: u64 = 4096;
+
+/// Every heap block starts with one of these. Blocks are 16 byte aligned.
+#[repr(C)]
+struct Block {
+    size: u64, // bytes after this header
+    next: u64, // offset of the next free block from the heap base, 0 for none
+}
+
+/// The start of every process' heap. All processes share ulibs' statics,
+/// so this is where the allocator keeps its state.
+#[repr(C)]
+struct HeapHdr {
+    free: u64, // offset of the first free block, 0 for none
+    top: u64,  // offset of the first byte no block has used yet
+    end: u64,  // offset of the break
+}
+
+const BLOCK_HDR: u64 = core::mem::size_of::<Block>() as u64;
+const HEAP_HDR: u64 = core::mem::size_of::<HeapHdr>() as u64;
+
+///
+/// User heap allocator. First fit over a free list, growing the heap with
+/// sys_brk when nothing fits. Hooked up with #[global_allocator] so user
+/// programs can use Box, Vec and String. It keeps its own idea of where
+/// the break is, so a program using it shouldn't move the break itself.
+///
+pub struct UserHeap;
+
+impl UserHeap {
+    /// Finds the heap, setting it up the first time. It's always at
+    /// space::HEAP_BASE (touching it reserves it), so the only syscalls are
+    /// sys_brk calls to grow it.
+    fn base(&self) -> u64 {
+        let base = space::HEAP_BASE;
+        let hdr  = unsafe { &mut *(base as *mut HeapHdr) };
+        // A new heap is all zeroes; a set up one always has top past the header
+        if hdr.top == 0 {
+            if sys_brk(base + HEAP_STEP) < 0 {
+                return 0;
+            }
+            hdr.free = 0;
+            hdr.top  = HEAP_HDR;
+            hdr.end  = HEAP_STEP;
+        }
+        return base;
+    }
+}
+
+unsafe impl GlobalAlloc for UserHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let base = self.base();
+        if base == 0 {
+            return ptr::null_mut();
+        }
+        let hdr   = &mut *(base as *mut HeapHdr);
+        let align = if layout.align() < 16 { 16 } else { layout.align() as u64 };
+        let size  = (layout.size() as u64 + 15) & !15;
+
+        // First fit from the free list
+        let mut prev = 0;
+        let mut off  = hdr.free;
+        while off != 0 {
+            let blk = &mut *((base + off) as *mut Block);
+            if blk.size >= size && (base + off + BLOCK_HDR) % align == 0 {
+                // Split off whatever is left if it's worth keeping
+                let mut next = blk.next;
+                if blk.size - size >= BLOCK_HDR + 16 {
+                    let rest_off = off + BLOCK_HDR + size;
+                    let rest = &mut *((base + rest_off) as *mut Block);
+                    rest.size = blk.size - size - BLOCK_HDR;
+                    rest.next = blk.next;
+                    blk.size  = size;
+                    next      = rest_off;
+                }
+                if prev == 0 {
+                    hdr.free = next;
+                }
+                else {
+                    (*((base + prev) as *mut Block)).next = next;
+                }
+                return (base + off + BLOCK_HDR) as *mut u8;
+            }
+            prev = off;
+            off  = blk.next;
+        }
+
+        // Carve a new block off the top, padding so the data is aligned
+        let mut top = hdr.top;
+        while (base + top + BLOCK_HDR) % align != 0 {
+            top += 16;
+        }
+        let end = top + BLOCK_HDR + size;
+        if end > hdr.end {
+            let want = (end + HEAP_STEP - 1) & !(HEAP_STEP - 1);
+            if sys_brk(base + want) < 0 {
+                return ptr::null_mut();
+            }
+            hdr.end = want;
+        }
+        let blk  = &mut *((base + top) as *mut Block);
+        blk.size = size;
+        blk.next = 0;
+        hdr.top  = top + BLOCK_HDR + size;
+        return (base + top + BLOCK_HDR) as *mut u8;
+    }
+
+    unsafe fn dealloc(&self, p: *mut u8, _layout: Layout) {
+        // Anything alloc handed out means the heap is already set up
+        if p.is_null() {
+            return;
+        }
+        let base = space::HEAP_BASE;
+        let hdr = &mut *(base as *mut HeapHdr);
+        let off = p as u64 - BLOCK_HDR - base;
+        let blk = &mut *((base + off) as *mut Block);
+        blk.next = hdr.free;
+        hdr.free = off;
+    }
+}
+
+/// The user print family of macros. Same as print and println, but the
+/// output goes to stdout through sys_write instead of straight to the
+/// kernel's WRITER.
+#[macro_export]
+macro_rules! uprint {
+    ($($arg:tt)*) => ($crate::ulibs::_uprint(format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! uprintln {
+    () => ($crate::uprint!("\n"));
+    ($($arg:tt)*) => ($crate::uprint!("{}\n", format_args!($($arg)*)));
+}
+
+/// Most bytes uprint collects before it has to write them out
+const UPRINT_BUF: usize = 128;
+
+/// Formatting buffer for uprint. Fills up, then goes out in one sys_write.
+struct UWriter {
+    buf: [u8; UPRINT_BUF],
+    used: usize,
+}
+
+impl UWriter {
+    fn flush(&mut self) {
+        if self.used > 0 {
+            sys_write(common::STDOUT, &self.buf[..self.used]);
+            self.used = 0;
+        }
+    }
+}
+
+impl fmt::Write for UWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.bytes() {
+            if self.used == UPRINT_BUF {
+                self.flush();
+            }
+            self.buf[self.used] = c;
+            self.used += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Print that the user print macros use
+#[doc(hidden)]
+pub fn _uprint(args: fmt::Arguments) {
+    use core::fmt::Write;
+    let mut w = UWriter {
+        buf: [0; UPRINT_BUF],
+        used: 0,
+    };
+    w.write_fmt(args).unwrap();
+    w.flush();
+}
+
+///
+/// sys_progs - get information about one of the kernel's programs
+///
+/// usage: while sys_progs(i, &mut info) == common::E_SUCCESS { ... }
+///
+/// Returns:
+///     E_SUCCESS, or E_NO_DATA once index is past the last program
+///
+pub fn sys_progs(index:u64, info:&mut ProgInfo) -> i64 {
+    return syscall(syscalls::SYS_progs, index, info as *mut ProgInfo as u64, 0, 0);
+}
+
+///
+/// sys_procs - get information about one live process, including how much
+/// of its stacks it has used
+///
+/// usage: while sys_procs(i, &mut info) == common::E_SUCCESS { ... }
+///
+/// Returns:
+///     E_SUCCESS, or E_NO_DATA once index is past the last process
+///
+pub fn sys_procs(index:u64, info:&mut ProcInfo) -> i64 {
+    return syscall(syscalls::SYS_procs, index, info as *mut ProcInfo as u64, 0, 0);
+}
+
+///
+/// padded_str - turn a NUL padded name from the kernel into a &str
+///
+/// usage: uprintln!("{}", padded_str(&info.name))
+///
+pub fn padded_str(buf:&[u8]) -> &str {
+    let mut len = 0;
+    while len < buf.len() && buf[len] != 0 {
+        len += 1;
+    }
+    return match core::str::from_utf8(&buf[..len]) {
+        Ok(s) => s,
+        Err(_) => "",
+    };
+}
+
+///
+/// spawn - sys_spawn with the default attributes
+///
+/// usage: spawn("user_a", &["user_a", "hi"]);
+///
+/// An empty argv means just the program's name.
+///
+/// Returns:
+///     pid of the child, or an error from sys_spawn
 ///
-pub fn spawn(entry:u64) {
-    let new = sys_fork();
-    //println!("{}",new);
-    if new != 0 {
-        return;
+pub fn spawn(name:&str, argv:&[&str]) -> i64 {
+    if argv.is_empty() {
+        return sys_spawn(name, &[name], None);
     }
-    sys_exec(entry);
+    return sys_spawn(name, argv, None);
 }
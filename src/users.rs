@@ -10,28 +10,66 @@
 use crate::uprintln;
 use crate::uprint;
 use crate::ulibs;
+use crate::common;
+use crate::programs;
+use crate::scheduler;
+use crate::syscalls;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Programs init starts at boot, in order, and their arguments; no
+/// arguments means just the program's name
+const INIT_PROGRAMS: [(&str, &[&str]); 6] = [
+    ("idle", &[]),
+    ("progs", &[]),
+    ("user_a", &["user_a", "hello", "world"]),
+    ("syscall_bench", &[]),
+    ("heap_demo", &[]),
+    ("fuzz", &[]),
+];
+
+///
+/// register_programs - adds the programs in this file to the registry
+///
+pub fn register_programs() {
+    programs::register("init", "starts the boot programs and reaps orphans",
+                       init, common::PRIO_HIGH);
+    programs::register("idle", "runs when nothing else can", idle, common::PRIO_LOW);
+    programs::register("progs", "lists the registered programs", progs, common::PRIO_STD);
+    programs::register("ps", "lists processes and their stack use", ps, common::PRIO_STD);
+    programs::register("user_a", "prints its arguments and some a's", user_a, common::PRIO_STD);
+    programs::register("syscall_bench", "times syscall against int 0x42",
+                       syscall_bench, common::PRIO_STD);
+    programs::register("heap_demo", "uses Box, Vec and String", heap_demo, common::PRIO_STD);
+    programs::register("sandbox", "shows off sys_restrict", sandbox, common::PRIO_STD);
+    programs::register("fuzz", "throws random syscalls at the kernel", fuzz, common::PRIO_LOW);
+}
 
 ///
 /// init
-/// Description: The init process. Spawns the idle process and a user.
+/// Description: The init process. Spawns the programs in INIT_PROGRAMS.
 /// Returns: status, although nothing ever picks this up :/
 ///
 #[no_mangle]
-pub fn init() -> i32 {
-    uprintln!("Spawning Idle");
-    let entry = (idle as *mut fn()->i32) as u64;
-    ulibs::spawn(entry);
-
-    uprintln!("Spawning A");
-    let entry2 = (user_a as *mut fn()->i32) as u64;
-    ulibs::spawn(entry2);
+pub extern "C" fn init(_argc:u64, _argv:*const *const u8, _envp:*const *const u8) -> i32 {
+    for (name, argv) in INIT_PROGRAMS.iter() {
+        uprintln!("Spawning {}", name);
+        let pid = ulibs::spawn(name, argv);
+        if pid < 0 {
+            uprintln!("init: couldn't spawn {} ({})", name, pid);
+        }
+    }
 
     let pid = ulibs::sys_pid();
     let ppid = ulibs::sys_ppid();
     uprintln!("pid {}, ppid {}",pid,ppid);
     loop{
         let whom = ulibs::sys_wait();
-        uprintln!("Init reporting {} exited", whom);
+        if whom >= 0 {
+            uprintln!("Init reporting {} exited", whom);
+        }
     }
 
     return 1;
@@ -42,27 +80,81 @@ pub fn init() -> i32 {
 /// Description: The idle process. Runs when nothing else is.
 /// Returns: status, although nothing ever picks this up :/
 ///
-fn idle() -> i32 {
+extern "C" fn idle(_argc:u64, _argv:*const *const u8, _envp:*const *const u8) -> i32 {
     uprintln!("IDLE");
     let pid = ulibs::sys_pid();
     let ppid = ulibs::sys_ppid();
     uprintln!("pid {}, ppid {}",pid,ppid);
     loop{
-        let time = ulibs::sys_time();
+        let time = ulibs::fast_time();
         if time % 0x10000 == 0 {
             uprint!(".")
         }
     }
 }
 
+///
+/// progs
+/// Description: Lists every registered program with its priority and
+///              description.
+/// Returns: status, although nothing ever picks this up :/
+///
+extern "C" fn progs(_argc:u64, _argv:*const *const u8, _envp:*const *const u8) -> i32 {
+    let mut info = common::ProgInfo {
+        name: [0; common::MAX_PROG_NAME],
+        desc: [0; common::MAX_PROG_DESC],
+        prio: 0,
+    };
+    let mut i = 0;
+    while ulibs::sys_progs(i, &mut info) == common::E_SUCCESS {
+        uprintln!("{:16} prio {}  {}", ulibs::padded_str(&info.name), info.prio,
+                  ulibs::padded_str(&info.desc));
+        i += 1;
+    }
+    return 0;
+}
+
+///
+/// ps
+/// Description: Lists every live process with how much of its user and
+///              kernel stacks it has ever used.
+/// Returns: status, although nothing ever picks this up :/
+///
+extern "C" fn ps(_argc:u64, _argv:*const *const u8, _envp:*const *const u8) -> i32 {
+    let mut info = common::ProcInfo {
+        pid: 0,
+        ppid: 0,
+        state: 0,
+        prio: 0,
+        _pad: [0; 2],
+        stack_used: 0,
+        stack_limit: 0,
+        kstack_used: 0,
+        kstack_size: 0,
+    };
+    let mut i = 0;
+    uprintln!("  pid  ppid state prio       stack         kstack");
+    while ulibs::sys_procs(i, &mut info) == common::E_SUCCESS {
+        uprintln!("{:5} {:5} {:5} {:4} {:7}/{:7} {:6}/{:6}", info.pid, info.ppid,
+                  info.state, info.prio, info.stack_used, info.stack_limit,
+                  info.kstack_used, info.kstack_size);
+        i += 1;
+    }
+    return 0;
+}
+
 ///
 /// user_a
-/// Description: A user process. Prints a bunch of 'a's and exits.
+/// Description: A user process. Prints its arguments, then a bunch of 'a's
+///              and exits.
 /// Returns: status, although nothing ever picks this up :/
 ///
-fn user_a() -> i32 {
+extern "C" fn user_a(argc:u64, argv:*const *const u8, _envp:*const *const u8) -> i32 {
+    for i in 0..argc {
+        uprintln!("argv[{}] = {}", i, ulibs::arg(argv, i));
+    }
     for i in 0..10000 {
-        let time = ulibs::sys_time();
+        let time = ulibs::fast_time();
         if time % 0x10 == 0 {
             uprint!("a")
         }
@@ -70,3 +162,196 @@ fn user_a() -> i32 {
     uprintln!();
     return 0;
 }
+
+/// Calls per syscall path in syscall_bench
+const BENCH_CALLS: u64 = 1000;
+
+///
+/// syscall_bench
+/// Description: Times sys_pid through SYSCALL and through int 0x42 and
+///              prints the average cost of each in TSC ticks.
+/// Returns: status, although nothing ever picks this up :/
+///
+extern "C" fn syscall_bench(_argc:u64, _argv:*const *const u8, _envp:*const *const u8) -> i32 {
+    let start = ulibs::read_tsc();
+    for _ in 0..BENCH_CALLS {
+        ulibs::sys_pid();
+    }
+    let fast = ulibs::read_tsc() - start;
+
+    let start = ulibs::read_tsc();
+    for _ in 0..BENCH_CALLS {
+        ulibs::sys_pid_int();
+    }
+    let slow = ulibs::read_tsc() - start;
+
+    uprintln!("syscall: {} ticks/call, int 0x42: {} ticks/call",
+              fast / BENCH_CALLS, slow / BENCH_CALLS);
+    return 0;
+}
+
+///
+/// heap_demo
+/// Description: Exercises the user heap with a Box, a Vec and a String.
+/// Returns: status, although nothing ever picks this up :/
+///
+extern "C" fn heap_demo(_argc:u64, _argv:*const *const u8, _envp:*const *const u8) -> i32 {
+    let boxed = Box::new(42u64);
+    let mut v: Vec<u64> = Vec::new();
+    for i in 0..100 {
+        v.push(i * i);
+    }
+    let mut s = String::from("heap");
+    s.push_str(" works");
+    uprintln!("{}: box {}, {} squares summing to {}", s, boxed, v.len(),
+              v.iter().sum::<u64>());
+    return 0;
+}
+
+///
+/// sandbox
+/// Description: Restricts itself to a few syscalls, shows a denied one
+///              failing, then switches to the kill policy and gets killed.
+/// Returns: status, although nothing ever picks this up :/
+///
+extern "C" fn sandbox(_argc:u64, _argv:*const *const u8, _envp:*const *const u8) -> i32 {
+    let allowed = (1 << syscalls::SYS_write) | (1 << syscalls::SYS_pid) |
+                  (1 << syscalls::SYS_restrict);
+    ulibs::sys_restrict(allowed, common::SYSPOL_EPERM);
+    uprintln!("sandbox {}: fork says {}", ulibs::sys_pid(), ulibs::sys_fork());
+
+    ulibs::sys_restrict(allowed, common::SYSPOL_KILL);
+    uprintln!("sandbox: calling sys_time, which should kill us");
+    ulibs::sys_time();
+    uprintln!("sandbox: still alive, restrict is broken");
+    return 1;
+}
+
+/// How many workers fuzz runs, one after another
+const FUZZ_WORKERS: u64 = 3;
+
+/// How many syscalls each fuzz worker makes
+const FUZZ_CALLS: u64 = 500;
+
+/// Seed for the first fuzz worker; 0 means take one from the TSC
+const FUZZ_SEED: u64 = 0;
+
+/// Size of the buffer fuzz workers point syscalls at
+const FUZZ_SCRATCH: usize = 64;
+
+/// Most children a fuzz worker leaves running before it waits for them,
+/// so a worker can't fill the process table (NUM_PROC) by itself
+const FUZZ_KIDS: u8 = scheduler::NUM_PROC / 2;
+
+/// Argument values that tend to find bugs
+const FUZZ_EDGES: [u64; 14] = [
+    0, 1, 2, 7, 8, 16, 0xff, 0x1000, 0xb8000, 0xffff_ffff,
+    0x7fff_ffff_ffff_ffff, 0x8000_0000_0000_0000, !1, !0,
+];
+
+/// xorshift64*, good enough for picking syscall arguments
+struct FuzzRng {
+    state: u64,
+}
+
+impl FuzzRng {
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        return self.state.wrapping_mul(0x2545_f491_4f6c_dd1d);
+    }
+}
+
+///
+/// fuzz
+/// Description: Syscall fuzzer. Run with no arguments (or just a seed, in
+///              hex) it spawns FUZZ_WORKERS workers one at a time, logging
+///              each one's seed, and checks the kernel is still there after
+///              each. "fuzz worker <seed>" is one worker, and reruns it.
+/// Returns: status, although nothing ever picks this up :/
+///
+extern "C" fn fuzz(argc:u64, argv:*const *const u8, _envp:*const *const u8) -> i32 {
+    if argc == 3 && ulibs::arg(argv, 1) == "worker" {
+        let seed = u64::from_str_radix(ulibs::arg(argv, 2), 16).unwrap_or(1);
+        fuzz_worker(seed);
+        return 0;
+    }
+
+    let mut seed = FUZZ_SEED;
+    if argc == 2 {
+        seed = u64::from_str_radix(ulibs::arg(argv, 1), 16).unwrap_or(FUZZ_SEED);
+    }
+    if seed == 0 {
+        seed = ulibs::read_tsc();
+    }
+
+    for w in 0..FUZZ_WORKERS {
+        let wseed = seed.wrapping_add(w) | 1;
+        let seed_str = format!("{:x}", wseed);
+        uprintln!("fuzz: worker {} seed {}", w, seed_str);
+        let pid = ulibs::spawn("fuzz", &["fuzz", "worker", &seed_str]);
+        if pid < 0 {
+            uprintln!("fuzz: spawn failed ({})", pid);
+            continue;
+        }
+        ulibs::sys_wait();
+        uprintln!("fuzz: worker {} (pid {}) done, kernel still alive at time {}",
+                  w, pid, ulibs::sys_time());
+    }
+    uprintln!("fuzz: all {} workers done", FUZZ_WORKERS);
+    return 0;
+}
+
+///
+/// fuzz_worker - makes FUZZ_CALLS random syscalls
+///
+/// Every syscall in the table is fair game, fork, exec and spawn included.
+/// A forked copy of the worker exits right away, and the worker waits for
+/// its children once it has FUZZ_KIDS of them. Once in a while the code is
+/// anything at all, which should get the worker killed with EXIT_BAD_CODE.
+///
+fn fuzz_worker(seed: u64) {
+    let mut rng = FuzzRng { state: seed };
+    let mut scratch = [b'z'; FUZZ_SCRATCH];
+    let scratch_addr = scratch.as_mut_ptr() as u64;
+    let mut kids = 0;
+
+    for _ in 0..FUZZ_CALLS {
+        let code = if rng.next() % 256 == 0 {
+            FUZZ_EDGES[(rng.next() % FUZZ_EDGES.len() as u64) as usize]
+        }
+        else {
+            let n = syscalls::SYSCALLS.len() as u64;
+            syscalls::SYSCALLS[(rng.next() % n) as usize].code as u64
+        };
+        // Never exit on purpose, it would end the run early
+        if code == syscalls::SYS_exit as u64 {
+            continue;
+        }
+
+        let mut args = [0u64; 6];
+        for a in args.iter_mut() {
+            *a = match rng.next() % 4 {
+                0 => FUZZ_EDGES[(rng.next() % FUZZ_EDGES.len() as u64) as usize],
+                1 => rng.next() % 32,
+                2 => scratch_addr + rng.next() % FUZZ_SCRATCH as u64,
+                _ => rng.next(),
+            };
+        }
+        let ret = ulibs::raw_syscall(code, &args);
+
+        if code == syscalls::SYS_fork as u64 && ret == 0 {
+            ulibs::sys_exit(common::EXIT_SUCCESS);
+        }
+        if (code == syscalls::SYS_fork as u64 || code == syscalls::SYS_spawn as u64) && ret > 0 {
+            kids += 1;
+            if kids >= FUZZ_KIDS {
+                // Stops early if a fuzzed restrict took wait away
+                while ulibs::sys_wait() >= 0 {}
+                kids = 0;
+            }
+        }
+    }
+    while ulibs::sys_wait() >= 0 {}
+}
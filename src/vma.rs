@@ -0,0 +1,223 @@
+///
+/// vma.rs
+///
+/// Author: Jonathan Schenk
+///
+/// Virtual memory areas: the anonymous regions a process has mapped with
+/// sys_mmap. Each process keeps a small table of them in its Pcb.
+///
+/// A region is backed by frames mapped into the process at a fixed address
+/// for its slot in the table, so a forked child finds its copy where the
+/// parent had the original; the two share the frames copy-on-write. Its
+/// protection goes into the page table entries, and uaccess.rs checks it
+/// for the kernel's copies.
+///
+////////////////////////////////////////////////////////////////////////////////
+
+use crate::common;
+use crate::paging;
+use crate::pcbs;
+use crate::space;
+
+/// How many regions each process can map
+pub const MAX_VMAS: usize = 8;
+
+/// Size of a page; regions start on one and are a whole number of them
+pub const PAGE_SIZE: u64 = 4096;
+
+/// One mapped region
+#[derive(Clone, Copy)]
+pub struct Vma {
+    pub start: u64, // first byte, page aligned
+    pub len: u64,   // length, a multiple of PAGE_SIZE
+    pub prot: u64,  // common::PROT_* bits
+}
+
+/// A process' regions
+pub type VmaTable = [Option<Vma>; MAX_VMAS];
+
+///
+/// map - maps a new zeroed region into a process
+///
+/// params:
+///     pcb: the process
+///     len: how many bytes it wants; rounded up to whole pages
+///     prot: common::PROT_* bits
+///
+/// returns:
+///     start of the region, or E_BAD_ARGS or E_NO_MEM
+///
+pub fn map(pcb: &mut pcbs::Pcb, len: u64, prot: u64) -> i64 {
+    if len == 0 || len > common::MMAP_MAX || prot & !common::PROT_ALL != 0 {
+        return common::E_BAD_ARGS;
+    }
+    let len = (len + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+
+    let slot = match pcb.vmas.iter().position(|v| v.is_none()) {
+        Some(slot) => slot,
+        None => return common::E_NO_MEM,
+    };
+
+    let v = Vma {
+        start: space::MMAP_BASE + slot as u64 * common::MMAP_MAX,
+        len: len,
+        prot: prot,
+    };
+    if pcb.space.map_frames(v.start, v.len, space::USER_FLAGS) != common::E_SUCCESS {
+        return common::E_NO_MEM;
+    }
+    apply(&mut pcb.space, &v);
+    pcb.vmas[slot] = Some(v);
+    return v.start as i64;
+}
+
+///
+/// unmap - removes a region from a process
+///
+/// Only whole regions can be unmapped, exactly as sys_mmap returned them.
+///
+/// params:
+///     pcb: the process
+///     start: start of the region
+///     len: its length; rounded up to whole pages
+///
+/// returns:
+///     E_SUCCESS or E_BAD_ARGS
+///
+pub fn unmap(pcb: &mut pcbs::Pcb, start: u64, len: u64) -> i64 {
+    let slot = match find_exact(&pcb.vmas, start, len) {
+        Some(slot) => slot,
+        None => return common::E_BAD_ARGS,
+    };
+    if let Some(v) = pcb.vmas[slot] {
+        pcb.space.unmap_frames(v.start, v.len);
+    }
+    pcb.vmas[slot] = None;
+    return common::E_SUCCESS;
+}
+
+///
+/// protect - changes the protection of a region
+///
+/// params:
+///     pcb: the process
+///     start: start of the region
+///     len: its length; rounded up to whole pages
+///     prot: the new common::PROT_* bits
+///
+/// returns:
+///     E_SUCCESS or E_BAD_ARGS
+///
+pub fn protect(pcb: &mut pcbs::Pcb, start: u64, len: u64, prot: u64) -> i64 {
+    if prot & !common::PROT_ALL != 0 {
+        return common::E_BAD_ARGS;
+    }
+    let slot = match find_exact(&pcb.vmas, start, len) {
+        Some(slot) => slot,
+        None => return common::E_BAD_ARGS,
+    };
+    let mut v = match pcb.vmas[slot] {
+        Some(v) => v,
+        None => return common::E_BAD_ARGS,
+    };
+    v.prot = prot;
+    apply(&mut pcb.space, &v);
+    pcb.vmas[slot] = Some(v);
+    return common::E_SUCCESS;
+}
+
+///
+/// find - finds the region an address is in
+///
+/// params:
+///     vmas: the process' regions
+///     addr: the address
+///
+/// returns:
+///     a copy of the region, or None if addr isn't mapped
+///
+pub fn find(vmas: &VmaTable, addr: u64) -> Option<Vma> {
+    for v in vmas.iter() {
+        if let Some(v) = v {
+            if addr >= v.start && addr < v.start + v.len {
+                return Some(*v);
+            }
+        }
+    }
+    return None;
+}
+
+///
+/// copy_all - gives a forked child every region, at the same addresses.
+/// The frames are shared copy-on-write, so nothing is copied until one
+/// of the two writes to a page. Regions there's no room in the child's
+/// page tables for are left out of it.
+///
+/// params:
+///     child: the new process, with no regions yet
+///     parent: the process it was forked from
+///
+pub fn copy_all(child: &mut pcbs::Pcb, parent: &pcbs::Pcb) {
+    for i in 0..MAX_VMAS {
+        child.vmas[i] = None;
+        if let Some(v) = parent.vmas[i] {
+            if parent.space.share_cow(&mut child.space, v.start, v.len) != common::E_SUCCESS {
+                child.space.unmap_frames(v.start, v.len);
+                continue;
+            }
+            child.vmas[i] = Some(v);
+        }
+    }
+}
+
+///
+/// unmap_all - removes every region, for exit and exec
+///
+pub fn unmap_all(pcb: &mut pcbs::Pcb) {
+    for i in 0..MAX_VMAS {
+        if let Some(v) = pcb.vmas[i] {
+            pcb.space.unmap_frames(v.start, v.len);
+        }
+        pcb.vmas[i] = None;
+    }
+}
+
+///
+/// apply - sets a region's pages up the way its protection says.
+/// PROT_NONE makes them inaccessible; without PROT_WRITE they're
+/// read-only, and without PROT_EXEC they're no-execute.
+///
+/// params:
+///     pt: the process' page tables
+///     v: the region
+///
+fn apply(pt: &mut paging::PageTable, v: &Vma) {
+    if v.prot & common::PROT_ALL == common::PROT_NONE {
+        pt.protect_range(v.start, v.len, paging::PG_NONE);
+        return;
+    }
+    let mut flags = paging::PG_USER;
+    if v.prot & common::PROT_WRITE != 0 {
+        flags |= paging::PG_WRITE;
+    }
+    if v.prot & common::PROT_EXEC == 0 {
+        flags |= paging::PG_NX;
+    }
+    pt.protect_range(v.start, v.len, flags);
+}
+
+/// Finds the slot of the region that is exactly [start, start + len)
+fn find_exact(vmas: &VmaTable, start: u64, len: u64) -> Option<usize> {
+    if len > common::MMAP_MAX {
+        return None;
+    }
+    let len = (len + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+    for i in 0..MAX_VMAS {
+        if let Some(v) = vmas[i] {
+            if v.start == start && v.len == len {
+                return Some(i);
+            }
+        }
+    }
+    return None;
+}
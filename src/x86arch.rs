@@ -3,7 +3,8 @@
 ///
 /// author: Jonathan Schenk?
 ///
-/// This file contains the necessary definitions from x86arch.h.
+/// This file contains the necessary definitions from x86arch.h, and the
+/// serial port ones from uart.h.
 /// Rust can't use C header files so this file was necessary.
 ///
 ////////////////////////////////////////////////////////////////////////////////
@@ -11,6 +12,26 @@
 
 pub static EFLAGS_MB1: i32 = 0x00000002;
 pub static EFLAGS_IF: i32 = 0x00000200;
+pub static EFLAGS_TF: i32 = 0x00000100;
+pub static EFLAGS_DF: i32 = 0x00000400;
+
+pub static MSR_EFER: u32 = 0xC0000080;
+pub static MSR_STAR: u32 = 0xC0000081;
+pub static MSR_LSTAR: u32 = 0xC0000082;
+pub static MSR_SFMASK: u32 = 0xC0000084;
+
+pub static EFER_SCE: u64 = 0x00000001;
+pub static EFER_NXE: u64 = 0x00000800;
+
+pub static CR0_WP: u64 = 0x00010000;
+
+pub static GDT64_ADDRESS: u64 = 0x1000;
+pub static GDT64_CODE: u64 = 0x0008;
+pub static GDT64_DATA: u64 = 0x0010;
+pub static GDT64_UDATA: u64 = 0x0018;
+pub static GDT64_UCODE: u64 = 0x0020;
+pub static GDT64_TSS: u64 = 0x0028;
+pub static RPL_USER: u64 = 0x0003;
 
 pub static TIMER_BASE_PORT: i32 = 0x40;
 pub static TIMER_0_PORT: i32 = (TIMER_BASE_PORT);
@@ -28,13 +49,18 @@ pub static TIMER_MODE_5: i32 = 0x0a;
 pub static TIMER_0_LOAD: i32 = 0x30;
 pub static TIMER_0_SQUARE: i32 = TIMER_MODE_3;
 
+pub static INT_VEC_DOUBLE_FAULT: usize = 0x08;
+pub static INT_VEC_GENERAL_PROTECTION: usize = 0x0d;
+pub static INT_VEC_PAGE_FAULT: usize = 0x0e;
 pub static INT_VEC_KEYBOARD: usize = 0x21;
 pub static INT_VEC_TIMER: usize = 0x20;
 pub static INT_VEC_MYSTERY: usize = 0x27;
 
 pub static IDT_PRESENT: usize = 0x8000;
 pub static IDT_DPL_0: usize = 0x0000;
+pub static IDT_DPL_3: usize = 0x6000;
 pub static IDT_INT32_GATE: usize = 0x0e00;
+pub static IDT_IST_MASK: usize = 0x0007;
 
 pub static PIC_NEEDICW4: i32 = 0x01;
 pub static PIC_ICW1BASE: i32 = 0x10;
@@ -47,3 +73,26 @@ pub static PIC_SLAVE_CMD_PORT: i32 = 0xA0;
 pub static PIC_SLAVE_IMR_PORT: i32 = 0xA1;
 pub static PIC_MASTER_SLAVE_LINE: i32 = 0x04;
 pub static PIC_SLAVE_ID: i32 = 0x02;
+
+pub static UA4_PORT: i32 = 0x3f8; // COM1
+pub static UA4_RXD: i32 = (UA4_PORT + 0);
+pub static UA4_TXD: i32 = (UA4_PORT + 0);
+pub static UA4_IER: i32 = (UA4_PORT + 1);
+pub static UA4_FCR: i32 = (UA4_PORT + 2);
+pub static UA4_LCR: i32 = (UA4_PORT + 3);
+pub static UA4_MCR: i32 = (UA4_PORT + 4);
+pub static UA4_LSR: i32 = (UA4_PORT + 5);
+pub static UA4_LBGD_L: i32 = (UA4_PORT + 0);
+pub static UA4_LBGD_H: i32 = (UA4_PORT + 1);
+
+pub static UA5_FCR_FIFO_EN: i32 = 0x01;
+pub static UA5_FCR_RXSR: i32 = 0x02;
+pub static UA5_FCR_TXSR: i32 = 0x04;
+pub static UA4_LCR_BKSE: i32 = 0x80;
+pub static UA4_LCR_BITS_8: i32 = 0x03;
+pub static UA4_MCR_DTR: i32 = 0x01;
+pub static UA4_MCR_RTS: i32 = 0x02;
+pub static UA4_LSR_RXDA: i32 = 0x01;
+pub static UA4_LSR_TXRDY: i32 = 0x20;
+
+pub static BAUD_38400: i32 = 3;